@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use reqwest::Client;
+
+use crate::{SmartherError, API_URL, AUTH_URL, TOKEN_URL};
+
+/// Endpoints and HTTP settings used by [`SmartherApi`](crate::SmartherApi), defaulting to the Legrand production services.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmartherConfig {
+    pub api_url: String,
+    pub auth_url: String,
+    pub token_url: String,
+    pub timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Upper bound of the requests issued concurrently by bulk operations such as `get_plant_status`
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for SmartherConfig {
+    fn default() -> Self {
+        Self {
+            api_url: API_URL.into(),
+            auth_url: AUTH_URL.into(),
+            token_url: TOKEN_URL.into(),
+            timeout: None,
+            connect_timeout: None,
+            max_concurrent_requests: 4,
+        }
+    }
+}
+
+impl SmartherConfig {
+    pub fn with_api_url(mut self, api_url: impl Into<String>) -> Self {
+        self.api_url = api_url.into();
+        self
+    }
+
+    pub fn with_auth_url(mut self, auth_url: impl Into<String>) -> Self {
+        self.auth_url = auth_url.into();
+        self
+    }
+
+    pub fn with_token_url(mut self, token_url: impl Into<String>) -> Self {
+        self.token_url = token_url.into();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = max_concurrent_requests.max(1);
+        self
+    }
+
+    pub(crate) fn build_client(&self) -> Result<Client, SmartherError> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        Ok(builder.build()?)
+    }
+}
@@ -0,0 +1,58 @@
+use futures::{StreamExt, TryStreamExt};
+
+use crate::{
+    model::{Module, ModuleStatus, Plant, PlantId},
+    states::Authorized,
+    SmartherApi, SmartherError,
+};
+
+/// A module found while walking the account, along with the plant it belongs to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredModule {
+    pub plant: Plant,
+    pub module: Module,
+}
+
+/// Status of a single module fetched by [`SmartherApi::get_plant_status`].
+#[derive(Debug)]
+pub struct ModuleStatusResult {
+    pub module: Module,
+    pub status: Result<ModuleStatus, SmartherError>,
+}
+
+impl SmartherApi<Authorized> {
+    /// Lists every module of every plant, fetching at most `max_concurrent_requests` plant topologies at a time.
+    pub async fn discover(&self) -> Result<Vec<DiscoveredModule>, SmartherError> {
+        let plants = self.get_plants().await?.plants;
+        let topologies: Vec<_> = futures::stream::iter(&plants)
+            .map(|plant| self.get_topology(&plant.id))
+            .buffered(self.config.max_concurrent_requests.max(1))
+            .try_collect()
+            .await?;
+
+        Ok(plants.into_iter()
+            .zip(topologies)
+            .flat_map(|(plant, topology)| {
+                topology.plant.modules.into_iter().map(move |module| DiscoveredModule {
+                    plant: plant.clone(),
+                    module,
+                })
+            })
+            .collect())
+    }
+
+    /// Fetches the status of every module of the plant, at most `max_concurrent_requests` at a time.
+    /// Failures are reported per module, only a failure resolving the topology fails the whole call.
+    pub async fn get_plant_status(&self, plant_id: &PlantId) -> Result<Vec<ModuleStatusResult>, SmartherError> {
+        let modules = self.get_topology(plant_id).await?.plant.modules;
+        let results = futures::stream::iter(modules)
+            .map(|module| async move {
+                let status = self.get_device_status(plant_id, &module.id).await;
+                ModuleStatusResult { module, status }
+            })
+            .buffered(self.config.max_concurrent_requests.max(1))
+            .collect()
+            .await;
+        Ok(results)
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SmartherError {
+    #[error("Unexpected HTTP status {status}: {body}")]
+    Http {
+        status: StatusCode,
+        body: String
+    },
+    #[error("Authorization expired or rejected")]
+    AuthExpired,
+    #[error("Rate limit exceeded")]
+    RateLimited {
+        retry_after: Option<Duration>
+    },
+    #[error("Validation failed: {0}")]
+    Validation(String),
+    #[error("Failed to deserialize response: {source}")]
+    Deserialization {
+        #[source]
+        source: serde_json::Error,
+        payload: String
+    },
+    #[error("Missing data in response: {0}")]
+    MissingData(String),
+    #[error("Authorization error: {0}")]
+    Authorization(String),
+    #[error("Transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl SmartherError {
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            SmartherError::Http { status, .. } => Some(*status),
+            SmartherError::AuthExpired => Some(StatusCode::UNAUTHORIZED),
+            SmartherError::RateLimited { .. } => Some(StatusCode::TOO_MANY_REQUESTS),
+            SmartherError::Transport(err) => err.status(),
+            _ => None
+        }
+    }
+
+    pub(crate) async fn from_response(response: reqwest::Response) -> Self {
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        match status {
+            StatusCode::UNAUTHORIZED => SmartherError::AuthExpired,
+            StatusCode::TOO_MANY_REQUESTS => SmartherError::RateLimited { retry_after },
+            _ => SmartherError::Http {
+                status,
+                body: response.text().await.unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// Parses `Retry-After`, either as delay in seconds or as HTTP date.
+pub(crate) fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_on = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (retry_on.with_timezone(&chrono::Utc) - chrono::Utc::now()).to_std().ok()
+        .or(Some(Duration::ZERO))
+}
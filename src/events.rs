@@ -0,0 +1,229 @@
+use std::{cmp::Ordering, collections::{HashMap, HashSet, VecDeque}};
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+
+use crate::model::{
+    C2CEvent, C2CEvents, Instrument, LoadState, Measurement, ModuleId, PlantId, ThermostatMode, ThermostatStatus, TimedMeasurement,
+};
+
+/// A change detected between two successive statuses of the same thermostat.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThermostatEvent {
+    ModeChanged {
+        previous: Option<ThermostatMode>,
+        current: ThermostatMode,
+    },
+    SetPointChanged {
+        previous: Option<Measurement>,
+        current: Option<Measurement>,
+    },
+    LoadStateChanged {
+        previous: Option<LoadState>,
+        current: Option<LoadState>,
+    },
+    TemperatureMeasured(TimedMeasurement),
+    HumidityMeasured(TimedMeasurement),
+    WentOffline,
+}
+
+/// A [`ThermostatEvent`] along with the module it happened on, delivered by both the polling and the webhook paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleEvent {
+    pub plant_id: PlantId,
+    pub module_id: ModuleId,
+    pub event: ThermostatEvent,
+}
+
+/// Lists the changes from `previous` to `current`, every known value is reported when there is no previous status.
+pub fn diff_status(previous: Option<&ThermostatStatus>, current: &ThermostatStatus) -> Vec<ThermostatEvent> {
+    let mut events = Vec::new();
+
+    let was_offline = previous.is_some_and(|previous| previous.online == Some(false));
+    if current.online == Some(false) && !was_offline {
+        events.push(ThermostatEvent::WentOffline);
+    }
+
+    if previous.map(|previous| &previous.mode) != Some(&current.mode) {
+        events.push(ThermostatEvent::ModeChanged {
+            previous: previous.map(|previous| previous.mode.clone()),
+            current: current.mode.clone(),
+        });
+    }
+
+    let previous_set_point = previous.and_then(|previous| previous.set_point.as_ref());
+    let set_point_changed = match previous {
+        None => current.set_point.is_some(),
+        Some(_) => !same_measurement(previous_set_point, current.set_point.as_ref()),
+    };
+    if set_point_changed {
+        events.push(ThermostatEvent::SetPointChanged {
+            previous: previous_set_point.cloned(),
+            current: current.set_point.clone(),
+        });
+    }
+
+    let previous_load_state = previous.and_then(|previous| previous.load_state.as_ref());
+    let load_state_changed = match previous {
+        None => current.load_state.is_some(),
+        Some(_) => previous_load_state != current.load_state.as_ref(),
+    };
+    if load_state_changed {
+        events.push(ThermostatEvent::LoadStateChanged {
+            previous: previous_load_state.cloned(),
+            current: current.load_state.clone(),
+        });
+    }
+
+    if let Some(measurement) = changed_measurement(
+        previous.and_then(|previous| previous.thermometer.as_ref()),
+        current.thermometer.as_ref(),
+    ) {
+        events.push(ThermostatEvent::TemperatureMeasured(measurement));
+    }
+
+    if let Some(measurement) = changed_measurement(
+        previous.and_then(|previous| previous.hygrometer.as_ref()),
+        current.hygrometer.as_ref(),
+    ) {
+        events.push(ThermostatEvent::HumidityMeasured(measurement));
+    }
+
+    events
+}
+
+fn same_measurement(previous: Option<&Measurement>, current: Option<&Measurement>) -> bool {
+    match (previous, current) {
+        (Some(previous), Some(current)) => previous.compare(current) == Some(Ordering::Equal),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn changed_measurement(previous: Option<&Instrument>, current: Option<&Instrument>) -> Option<TimedMeasurement> {
+    let current = current?.last_measurement()?;
+    let previous = previous.and_then(Instrument::last_measurement);
+    if same_measurement(previous.map(|previous| &previous.value), Some(&current.value)) {
+        None
+    } else {
+        Some(current.clone())
+    }
+}
+
+/// Number of event ids remembered by [`module_events`] to drop redelivered notifications.
+pub const DEFAULT_EVENT_ID_CAPACITY: usize = 1024;
+
+/// Remembers the last status of each module, turning new snapshots into [`ModuleEvent`]s.
+///
+/// Snapshots older than the last one seen for the module are ignored, so the tracked status never goes back in time.
+#[derive(Debug, Clone, Default)]
+pub struct StatusTracker {
+    statuses: HashMap<(PlantId, ModuleId), ThermostatStatus>,
+    event_times: HashMap<(PlantId, ModuleId), DateTime<Utc>>,
+    seen_events: Option<EventIdCache>,
+}
+
+impl StatusTracker {
+    /// Ignores the C2C events whose id is among the last `capacity` ones applied.
+    pub fn with_event_dedup(mut self, capacity: usize) -> Self {
+        self.seen_events = Some(EventIdCache::new(capacity));
+        self
+    }
+
+    pub fn last_status(&self, plant_id: &PlantId, module_id: &ModuleId) -> Option<&ThermostatStatus> {
+        self.statuses.get(&(plant_id.clone(), module_id.clone()))
+    }
+
+    pub fn update(&mut self, plant_id: &PlantId, module_id: &ModuleId, status: ThermostatStatus) -> Vec<ModuleEvent> {
+        let key = (plant_id.clone(), module_id.clone());
+        if self.statuses.get(&key).is_some_and(|previous| status.time < previous.time) {
+            return Vec::new();
+        }
+        let events = diff_status(self.statuses.get(&key), &status);
+        self.statuses.insert(key, status);
+
+        events.into_iter()
+            .map(|event| ModuleEvent {
+                plant_id: plant_id.clone(),
+                module_id: module_id.clone(),
+                event,
+            })
+            .collect()
+    }
+
+    /// Diffs every thermostat status carried by a C2C notification against the last known one.
+    ///
+    /// Redelivered events and events older than the last one applied to the module yield nothing.
+    pub fn apply_c2c_event(&mut self, event: &C2CEvent) -> Vec<ModuleEvent> {
+        if let Some(seen_events) = &mut self.seen_events {
+            if !seen_events.insert(&event.id) {
+                return Vec::new();
+            }
+        }
+
+        let mut module_events = Vec::new();
+        for (plant_id, module_id, status) in event.module_statuses() {
+            let key = (plant_id.clone(), module_id.clone());
+            if self.event_times.get(&key).is_some_and(|last_event_time| event.event_time < *last_event_time) {
+                continue;
+            }
+            self.event_times.insert(key, event.event_time);
+            module_events.extend(self.update(plant_id, module_id, status.clone()));
+        }
+        module_events
+    }
+}
+
+/// Turns the batches delivered by the webhook receiver into the same events yielded by `SmartherApi::watch`,
+/// dropping redelivered and out of order notifications.
+pub fn module_events(notifications: impl Stream<Item = C2CEvents>) -> impl Stream<Item = ModuleEvent> {
+    let mut tracker = StatusTracker::default().with_event_dedup(DEFAULT_EVENT_ID_CAPACITY);
+    notifications.flat_map(move |events| {
+        let module_events: Vec<ModuleEvent> = events.iter()
+            .flat_map(|event| tracker.apply_c2c_event(event))
+            .collect();
+        futures::stream::iter(module_events)
+    })
+}
+
+/// Remembers the most recent event ids, forgetting the oldest ones past `capacity`.
+#[derive(Debug, Clone)]
+pub struct EventIdCache {
+    capacity: usize,
+    ids: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl EventIdCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ids: HashSet::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.ids.contains(id)
+    }
+
+    /// Records the id, returns `false` if it was already seen.
+    pub fn insert(&mut self, id: &str) -> bool {
+        if !self.ids.insert(id.to_string()) {
+            return false;
+        }
+        self.order.push_back(id.to_string());
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        if self.ids.remove(id) {
+            self.order.retain(|seen| seen != id);
+        }
+    }
+}
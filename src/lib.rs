@@ -1,12 +1,21 @@
 #[macro_use] extern crate serde;
 
-use std::time::SystemTime;
+use std::{sync::{Arc, RwLock}, time::SystemTime};
 
-use anyhow::anyhow;
-use reqwest::Client;
+use futures::lock::Mutex;
+use log::{debug, warn};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
 use serde_json::json;
 use states::*;
 use model::*;
+use store::TokenStore;
+use retry::RetryPolicy;
+
+pub use config::SmartherConfig;
+pub use thermostat::Thermostat;
+
+pub use error::SmartherError;
 
 pub const API_URL: &str = "https://api.developer.legrand.com/smarther/v2.0";
 pub const AUTH_URL: &str = "https://partners-login.eliotbylegrand.com/authorize";
@@ -14,7 +23,16 @@ pub const TOKEN_URL: &str = "https://partners-login.eliotbylegrand.com/token";
 
 #[cfg(test)]
 mod test;
+pub mod config;
+pub mod discovery;
+pub mod error;
+pub mod events;
 pub mod model;
+pub mod retry;
+pub mod store;
+pub mod subscription;
+pub mod thermostat;
+pub mod watch;
 pub mod states {
     pub struct Unauthorized;
     pub struct Authorized;
@@ -22,6 +40,10 @@ pub mod states {
 
 #[cfg(feature = "web")]
 mod web;
+#[cfg(feature = "web")]
+pub mod webhook;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Hash, PartialOrd, Clone)]
 #[serde(untagged)]
@@ -53,36 +75,73 @@ impl AuthorizationInfo {
 }
 
 impl AuthorizationGrant {
-    pub fn request_token(&self) -> anyhow::Result<String> {
+    pub fn request_token(&self) -> Result<String, SmartherError> {
         if let AuthorizationGrant::OAuthToken { access_token, expires_on, .. } = self {
-            if *expires_on > SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?.as_secs() {
+            if *expires_on > now_secs() {
                 return Ok(access_token.clone());
             }
         }
-        Err(anyhow!("No valid request token found"))
+        Err(SmartherError::AuthExpired)
     }
 
     pub fn is_refresh_needed(&self) -> bool {
         if let AuthorizationGrant::OAuthToken { expires_on, .. } = self {
-            *expires_on < SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs()
+            *expires_on < now_secs()
         } else {
             true
         }
     }
 }
 
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 pub struct SmartherApi<State> {
-    auth_info: Option<AuthorizationInfo>,
+    auth_info: RwLock<Option<AuthorizationInfo>>,
+    refresh_lock: Mutex<()>,
+    token_store: Option<Arc<dyn TokenStore>>,
+    retry_policy: RetryPolicy,
+    config: SmartherConfig,
     client: Client,
     state: std::marker::PhantomData<State>,
 }
 
 impl Default for SmartherApi<Unauthorized> {
     fn default() -> Self {
-        Self {
-            auth_info: None,
-            client: Client::new(),
-            state: std::marker::PhantomData,
+        Self::from_parts(SmartherConfig::default(), Client::new())
+    }
+}
+
+impl<State> SmartherApi<State> {
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    async fn request_token(&self, auth_info: &AuthorizationInfo) -> Result<AuthorizationInfo, SmartherError> {
+        let refresh_request: OAuthTokenRequest = auth_info.try_into()?;
+        let response = self.client.post(&self.config.token_url)
+            .form(&refresh_request)
+            .send().await?;
+
+        let response = check_status(response, StatusCode::OK).await?;
+        let auth_token = parse_json(response).await?;
+        Ok(AuthorizationInfo {
+            grant: auth_token,
+            ..auth_info.clone()
+        })
+    }
+
+    /// Saves the authorization to the token store, if any. A failure is only logged, since the token
+    /// was already issued: dropping it could leave the client with a refresh token the provider revoked.
+    async fn persist(&self, auth_info: &AuthorizationInfo) {
+        if let Some(token_store) = &self.token_store {
+            if let Err(err) = token_store.save(auth_info).await {
+                warn!("Failed to save the refreshed authorization: {}", err);
+            }
         }
     }
 }
@@ -101,7 +160,7 @@ struct OAuthTokenRequest {
 }
 
 impl TryFrom<&AuthorizationInfo> for OAuthTokenRequest {
-    type Error = anyhow::Error;
+    type Error = SmartherError;
 
     fn try_from(info: &AuthorizationInfo) -> Result<Self, Self::Error> {
         let grant = &info.grant;
@@ -126,16 +185,68 @@ impl TryFrom<&AuthorizationInfo> for OAuthTokenRequest {
                     ..Default::default()
                 })
             },
-            _ => { Err(anyhow!("Unsupported grant type")) }
+            _ => { Err(SmartherError::Authorization("Unsupported grant type".into())) }
         }
     }
 }
 
+async fn check_status(response: Response, expected: StatusCode) -> Result<Response, SmartherError> {
+    if response.status() == expected {
+        Ok(response)
+    } else {
+        Err(SmartherError::from_response(response).await)
+    }
+}
+
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, SmartherError> {
+    let payload = response.text().await?;
+    serde_json::from_str(&payload)
+        .map_err(|source| SmartherError::Deserialization { source, payload })
+}
+
+fn auth_header(auth_info: &AuthorizationInfo) -> Result<(&'static str, String), SmartherError> {
+    Ok(("Authorization" , format!("Bearer {}", auth_info.grant.request_token()?)))
+}
 
+fn subscription_header(auth_info: &AuthorizationInfo) -> (&'static str, String) {
+    ("Ocp-Apim-Subscription-Key", auth_info.subscription_key.clone())
+}
+
+fn smarther_headers(auth_info: &AuthorizationInfo) -> Result<reqwest::header::HeaderMap, SmartherError> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let auth_header = auth_header(auth_info)?;
+    let subscription_header = subscription_header(auth_info);
+    let invalid_header = |_| SmartherError::Authorization("Credentials contain invalid header characters".into());
+    headers.insert(auth_header.0, auth_header.1.parse().map_err(invalid_header)?);
+    headers.insert(subscription_header.0, subscription_header.1.parse().map_err(invalid_header)?);
+    Ok(headers)
+}
 
 impl SmartherApi<Unauthorized> {
+    fn from_parts(config: SmartherConfig, client: Client) -> Self {
+        Self {
+            auth_info: RwLock::new(None),
+            refresh_lock: Mutex::new(()),
+            token_store: None,
+            retry_policy: RetryPolicy::default(),
+            config,
+            client,
+            state: std::marker::PhantomData,
+        }
+    }
+
+    pub fn with_config(config: SmartherConfig) -> Result<Self, SmartherError> {
+        let client = config.build_client()?;
+        Ok(Self::from_parts(config, client))
+    }
+
+    /// Uses a preconfigured client, the timeouts of the config are ignored in this case.
+    pub fn with_config_and_client(config: SmartherConfig, client: Client) -> Self {
+        Self::from_parts(config, client)
+    }
+
     #[cfg(feature = "web")]
-    pub async fn get_oauth_access_code(&self, client_id: &str, client_secret: &str, base_uri: Option<&str>, subscription_key: &str, listen_config: (&str, u16)) -> anyhow::Result<AuthorizationInfo> {
+    pub async fn get_oauth_access_code(&self, client_id: &str, client_secret: &str, base_uri: Option<&str>, subscription_key: &str, listen_config: (&str, u16)) -> Result<AuthorizationInfo, SmartherError> {
         use actix_web::{App, HttpServer, web::Data};
         use log::info;
 
@@ -147,17 +258,20 @@ impl SmartherApi<Unauthorized> {
             csrf_token: cross_code.clone()
         };
 
+        let auth_url = &self.config.auth_url;
         let hostname = listen_config.0;
         let port = listen_config.1;
         let redirect_url = format!("{}/tokens", base_uri.unwrap_or(format!("http://{hostname}:{port}").as_str()));
         let auth_code = tokio::select!(
             code = async move {
-                let oauth_link = format!("{AUTH_URL}?response_type=code&client_id={client_id}&state={cross_code}&redirect_uri={redirect_url}");
+                let oauth_link = format!("{auth_url}?response_type=code&client_id={client_id}&state={cross_code}&redirect_uri={redirect_url}");
                 info!("Please open the following link in your browser: {}", &oauth_link);
                 if open::that(&oauth_link).is_err() {
                     info!("Failed to open browser, please open the link manually");
                 }
-                rx.recv().await?
+                rx.recv().await
+                    .map_err(|_| SmartherError::Authorization("Authorization flow was interrupted".into()))?
+                    .map_err(|err| SmartherError::Authorization(err.to_string()))
             } => code,
             _ = async move {
                 HttpServer::new(move || {
@@ -168,7 +282,7 @@ impl SmartherApi<Unauthorized> {
                 .bind(listen_config)?
                 .run()
                 .await
-            } => Err(anyhow::anyhow!("Error binding local server to port 23784"))
+            } => Err(SmartherError::Authorization(format!("Error binding local server to port {port}")))
         )?;
 
         Ok(AuthorizationInfo { 
@@ -181,32 +295,82 @@ impl SmartherApi<Unauthorized> {
         })
     }
 
-    pub async fn refresh_token(&self, auth_info: &AuthorizationInfo) -> anyhow::Result<AuthorizationInfo> {
-        let refresh_request: OAuthTokenRequest = auth_info.try_into()?;
-        let response = self.client.post(TOKEN_URL)
-            .form(&refresh_request)
-            .send().await?;
+    /// Authorization flow for headless hosts, needing neither a browser nor a local server.
+    ///
+    /// `read_response` gets the authorize link to show to the user and returns what they paste back:
+    /// the URL they were redirected to, whose `state` is checked, or just the code it carries.
+    #[cfg(feature = "web")]
+    pub async fn get_oauth_access_code_with<F, Fut>(&self, client_id: &str, client_secret: &str, redirect_uri: &str, subscription_key: &str, read_response: F) -> Result<AuthorizationInfo, SmartherError>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: std::future::Future<Output = Result<String, SmartherError>>,
+    {
+        let cross_code = uuid::Uuid::new_v4().to_string();
+        let auth_url = &self.config.auth_url;
+        let oauth_link = format!("{auth_url}?response_type=code&client_id={client_id}&state={cross_code}&redirect_uri={redirect_uri}");
 
-        match response.status() {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(response.status().to_string())) }
-        }
+        let pasted = read_response(oauth_link).await?;
+        let auth_code = web::parse_pasted_response(&pasted, &cross_code)
+            .map_err(|err| SmartherError::Authorization(err.to_string()))?;
 
-        let token = response.text().await?;
-        let auth_token = serde_json::from_str(&token)?;
         Ok(AuthorizationInfo {
-            grant: auth_token,
-            ..auth_info.clone()
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            grant: AuthorizationGrant::AccessCode {
+                access_code: auth_code
+            },
+            subscription_key: subscription_key.to_string()
         })
     }
 
-    pub fn with_authorization(self, auth_info: AuthorizationInfo) -> anyhow::Result<SmartherApi<Authorized>> {
-        if auth_info.grant.is_refresh_needed() {
-            return Err(anyhow!("Authorization needs to be refreshed"))
+    /// Headless authorization flow printing the authorize link on stdout and reading the response from stdin.
+    #[cfg(feature = "web")]
+    pub async fn get_oauth_access_code_from_stdin(&self, client_id: &str, client_secret: &str, redirect_uri: &str, subscription_key: &str) -> Result<AuthorizationInfo, SmartherError> {
+        self.get_oauth_access_code_with(client_id, client_secret, redirect_uri, subscription_key, |oauth_link| async move {
+            println!("Please open the following link in a browser, then paste the URL you are redirected to:\n{oauth_link}");
+            tokio::task::spawn_blocking(|| {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line).map(|_| line)
+            })
+            .await
+            .map_err(|_| SmartherError::Authorization("Authorization flow was interrupted".into()))?
+            .map_err(SmartherError::from)
+        }).await
+    }
+
+    /// Persists every token obtained by this client, including the automatic refreshes of the authorized client.
+    pub fn with_token_store(mut self, token_store: impl TokenStore + 'static) -> Self {
+        self.token_store = Some(Arc::new(token_store));
+        self
+    }
+
+    pub async fn refresh_token(&self, auth_info: &AuthorizationInfo) -> Result<AuthorizationInfo, SmartherError> {
+        let refreshed = self.request_token(auth_info).await?;
+        self.persist(&refreshed).await;
+        Ok(refreshed)
+    }
+
+    /// Authorizes the client with the authorization saved in the configured token store.
+    pub async fn with_stored_authorization(self) -> Result<SmartherApi<Authorized>, SmartherError> {
+        let token_store = self.token_store.as_ref()
+            .ok_or_else(|| SmartherError::Authorization("No token store configured".into()))?;
+        let auth_info = token_store.load().await?
+            .ok_or_else(|| SmartherError::Authorization("No stored authorization found".into()))?;
+        self.with_authorization(auth_info)
+    }
+
+    /// Expired OAuth tokens are accepted, they get refreshed on the first request.
+    pub fn with_authorization(self, auth_info: AuthorizationInfo) -> Result<SmartherApi<Authorized>, SmartherError> {
+        if auth_info.grant == AuthorizationGrant::None {
+            return Err(SmartherError::Authorization("Missing authorization grant".into()))
         }
 
         Ok(SmartherApi {
-            auth_info: Some(auth_info),
+            auth_info: RwLock::new(Some(auth_info)),
+            refresh_lock: self.refresh_lock,
+            token_store: self.token_store,
+            retry_policy: self.retry_policy,
+            config: self.config,
             client: self.client,
             state: std::marker::PhantomData,
         })
@@ -214,129 +378,163 @@ impl SmartherApi<Unauthorized> {
 }
 
 impl SmartherApi<Authorized> {
-    fn auth_header(&self) -> anyhow::Result<(&'static str, String)> {
-        let auth_info = self.auth_info.as_ref().ok_or(anyhow!("Client should be authorized"))?;
-        Ok(("Authorization" , format!("Bearer {}", auth_info.grant.request_token()?)))
+    /// Returns the current authorization, including any token obtained through an automatic refresh.
+    pub fn authorization_info(&self) -> AuthorizationInfo {
+        self.auth_info.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+            .expect("Authorized client always holds an authorization")
     }
 
-    fn subscription_header(&self) -> anyhow::Result<(&'static str, String)> {
-        let auth_info = self.auth_info.as_ref().ok_or(anyhow!("Client should be authorized"))?;
-        Ok(("Ocp-Apim-Subscription-Key", auth_info.subscription_key.clone()))
+    /// Forces a token refresh and returns the updated authorization.
+    pub async fn refresh_authorization(&self) -> Result<AuthorizationInfo, SmartherError> {
+        let _guard = self.refresh_lock.lock().await;
+        self.store_refreshed(&self.authorization_info()).await
     }
 
-    fn smarther_headers(&self) -> anyhow::Result<reqwest::header::HeaderMap> {
-        let mut headers = reqwest::header::HeaderMap::new();
-        let auth_header = self.auth_header()?;
-        let subscription_header = self.subscription_header()?;
-        headers.insert(auth_header.0, auth_header.1.parse()?);
-        headers.insert(subscription_header.0, subscription_header.1.parse()?);
-        Ok(headers)
+    async fn refresh_stale(&self, stale: &AuthorizationInfo) -> Result<AuthorizationInfo, SmartherError> {
+        let _guard = self.refresh_lock.lock().await;
+        let current = self.authorization_info();
+        if current.grant != stale.grant {
+            // Another request already refreshed the token while we were waiting
+            return Ok(current);
+        }
+        self.store_refreshed(&current).await
     }
 
-    pub async fn get_plants(&self) -> anyhow::Result<Plants> {
-        let response = self.client.get(format!("{API_URL}/plants"))
-            .headers(self.smarther_headers()?)
-            .send().await?;
+    async fn store_refreshed(&self, auth_info: &AuthorizationInfo) -> Result<AuthorizationInfo, SmartherError> {
+        let refreshed = self.request_token(auth_info).await?;
+        *self.auth_info.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(refreshed.clone());
+        self.persist(&refreshed).await;
+        Ok(refreshed)
+    }
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
+    async fn send<F>(&self, build_request: F) -> Result<Response, SmartherError>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut auth_info = self.authorization_info();
+        if auth_info.is_refresh_needed() {
+            auth_info = self.refresh_stale(&auth_info).await?;
         }
-        
-        Ok(response.json().await?)
-    }
 
-    pub async fn get_topology(&self, plant_id: &str) -> anyhow::Result<PlantTopology> {
-        let response = self.client.get(format!("{API_URL}/plants/{plant_id}/topology"))
-            .headers(self.smarther_headers()?)
-            .send().await?;
+        let response = self.execute(&build_request, &auth_info).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let auth_info = self.refresh_stale(&auth_info).await?;
+        self.execute(&build_request, &auth_info).await
+    }
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
+    async fn execute<F>(&self, build_request: &F, auth_info: &AuthorizationInfo) -> Result<Response, SmartherError>
+    where
+        F: Fn(&Client) -> RequestBuilder,
+    {
+        let mut attempt = 0;
+        loop {
+            let request = build_request(&self.client)
+                .headers(smarther_headers(auth_info)?)
+                .build()?;
+            let can_retry = attempt < self.retry_policy.max_retries && self.retry_policy.allows(request.method());
+            let result = self.client.execute(request).await;
+
+            let retry_after = match &result {
+                Ok(response) if can_retry && retry::is_retryable_status(response.status()) => error::retry_after(response.headers()),
+                Err(err) if can_retry && retry::is_retryable_error(err) => None,
+                _ => return Ok(result?),
+            };
+
+            let Some(backoff) = self.retry_policy.backoff(attempt, retry_after) else {
+                return Ok(result?);
+            };
+            debug!("Retrying request in {:?} (attempt {})", backoff, attempt + 1);
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
         }
-        
-        Ok(response.json().await?)
     }
 
-    pub async fn get_device_status(&self, plant_id: &str, module_id: &str) -> anyhow::Result<ModuleStatus> {
-        let response = self.client.get(format!("{API_URL}/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}"))
-            .headers(self.smarther_headers()?)
-            .send().await?;
+    pub fn thermostat(&self, plant_id: PlantId, module_id: ModuleId) -> Thermostat<'_> {
+        Thermostat::new(self, plant_id, module_id)
+    }
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
-        }
-        
-        Ok(response.json().await?)
+    pub async fn get_plants(&self) -> Result<Plants, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.get(format!("{api_url}/plants"))).await?;
+
+        let response = check_status(response, StatusCode::OK).await?;
+        parse_json(response).await
     }
 
-    pub async fn set_device_status(&self, plant_id: &str, module_id: &str, status: SetStatusRequest) -> anyhow::Result<()> {
-        if !status.validate() {
-            return Err(anyhow::anyhow!("Invalid status"))
-        }
+    pub async fn get_topology(&self, plant_id: &PlantId) -> Result<PlantTopology, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.get(format!("{api_url}/plants/{plant_id}/topology"))).await?;
 
-        let response = self.client.post(format!("{API_URL}/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}"))
-            .headers(self.smarther_headers()?)
-            .json(&status)
-            .send().await?;
+        let response = check_status(response, StatusCode::OK).await?;
+        parse_json(response).await
+    }
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
-        }
-        
-        Ok(())
+    pub async fn get_device_status(&self, plant_id: &PlantId, module_id: &ModuleId) -> Result<ModuleStatus, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.get(format!("{api_url}/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}"))).await?;
+
+        let response = check_status(response, StatusCode::OK).await?;
+        parse_json(response).await
     }
 
-    pub async fn register_webhook(&self, plant_id: &str, endpoint_url: String) -> anyhow::Result<SubscriptionInfo> {
-        let response = self.client.post(format!("{API_URL}/plants/{plant_id}/subscription"))
-            .headers(self.smarther_headers()?)
-            .json(&json!({
-                "EndPointUrl": endpoint_url
-            }))
-            .send().await?;
+    pub async fn get_program_list(&self, plant_id: &PlantId, module_id: &ModuleId) -> Result<Vec<Program>, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.get(format!("{api_url}/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}/programlist"))).await?;
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::CREATED => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
-        }
-        
-        Ok(response.json().await?)
+        let response = check_status(response, StatusCode::OK).await?;
+        let program_list: ProgramList = parse_json(response).await?;
+        Ok(program_list.chronothermostats.into_iter()
+            .flat_map(|thermostat| thermostat.programs)
+            .collect())
     }
 
-    pub async fn unregister_webhook(&self, plant_id: &str, subscription_id: &str) -> anyhow::Result<()> {
-        let response = self.client.delete(format!("{API_URL}/plants/{plant_id}/subscription/{subscription_id}"))
-            .headers(self.smarther_headers()?)
-            .send().await?;
+    /// Sends the status change after checking its fields, use [`SetStatusRequest::validate_for`] beforehand
+    /// to also check the set point against the limits of the module.
+    pub async fn set_device_status(&self, plant_id: &PlantId, module_id: &ModuleId, status: SetStatusRequest) -> Result<(), SmartherError> {
+        status.validate_fields()?;
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
-        }
-        
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| {
+            client.post(format!("{api_url}/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}"))
+                .json(&status)
+        }).await?;
+
+        check_status(response, StatusCode::OK).await?;
         Ok(())
     }
 
-    pub async fn get_webhooks(&self) -> anyhow::Result<Vec<SubscriptionInfo>> {
-        let response = self.client.get(format!("{API_URL}/subscription"))
-            .headers(self.smarther_headers()?)
-            .send().await?;
+    pub async fn register_webhook(&self, plant_id: &PlantId, endpoint_url: String) -> Result<SubscriptionInfo, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| {
+            client.post(format!("{api_url}/plants/{plant_id}/subscription"))
+                .json(&json!({
+                    "EndPointUrl": endpoint_url
+                }))
+        }).await?;
+
+        let response = check_status(response, StatusCode::CREATED).await?;
+        parse_json(response).await
+    }
 
-        let status = response.status();
-        match status {
-            reqwest::StatusCode::OK => (),
-            _ => { return Err(anyhow::anyhow!(status.to_string())) }
-        }
-        
-        Ok(response.json().await?)
+    pub async fn unregister_webhook(&self, plant_id: &PlantId, subscription_id: &str) -> Result<(), SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.delete(format!("{api_url}/plants/{plant_id}/subscription/{subscription_id}"))).await?;
+
+        check_status(response, StatusCode::OK).await?;
+        Ok(())
+    }
+
+    pub async fn get_webhooks(&self) -> Result<Vec<SubscriptionInfo>, SmartherError> {
+        let api_url = &self.config.api_url;
+        let response = self.send(|client| client.get(format!("{api_url}/subscription"))).await?;
+
+        let response = check_status(response, StatusCode::OK).await?;
+        parse_json(response).await
     }
 
 }
\ No newline at end of file
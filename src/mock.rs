@@ -0,0 +1,635 @@
+//! Local stand-in for the Smarther API, to exercise [`SmartherApi`](crate::SmartherApi) without network access.
+
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Mutex, MutexGuard}};
+
+use actix_web::{
+    dev::ServerHandle,
+    http::StatusCode,
+    web::{self, Data, Form, Json, Path},
+    App, HttpRequest, HttpResponse, HttpServer,
+};
+
+use crate::{
+    model::{ModuleId, ModuleStatus, PlantDetail, PlantId, PlantTopology, Plants, Plant, Program, ProgramList, SetStatusRequest, SubscriptionInfo, ThermostatPrograms},
+    SmartherConfig,
+};
+
+const THERMOSTAT_PATH: &str = "/chronothermostat/thermoregulation/addressLocation/plants/{plant_id}/modules/parameter/id/value/{module_id}";
+
+/// Scripted state served by [`MockSmartherServer`], inspect it after a call to check what the client sent.
+#[derive(Debug, Clone)]
+pub struct MockState {
+    pub plants: Vec<Plant>,
+    pub topologies: HashMap<PlantId, PlantDetail>,
+    pub statuses: HashMap<(PlantId, ModuleId), ModuleStatus>,
+    pub programs: HashMap<(PlantId, ModuleId), Vec<Program>>,
+    pub subscriptions: Vec<SubscriptionInfo>,
+    /// Every status change received, in order
+    pub status_requests: Vec<(PlantId, ModuleId, SetStatusRequest)>,
+    /// Statuses returned, in order, by the next API requests instead of handling them
+    pub failures: VecDeque<StatusCode>,
+    /// The only bearer token accepted by the API endpoints
+    pub access_token: String,
+    /// Lifetime of the tokens issued by the token endpoint
+    pub token_lifetime_secs: u64,
+    pub issued_tokens: usize,
+    pub issued_subscriptions: usize,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            plants: Vec::new(),
+            topologies: HashMap::new(),
+            statuses: HashMap::new(),
+            programs: HashMap::new(),
+            subscriptions: Vec::new(),
+            status_requests: Vec::new(),
+            failures: VecDeque::new(),
+            access_token: "mock-access-token".into(),
+            token_lifetime_secs: 3600,
+            issued_tokens: 0,
+            issued_subscriptions: 0,
+        }
+    }
+}
+
+impl MockState {
+    /// Registers a plant and its topology.
+    pub fn with_plant(mut self, plant: PlantDetail) -> Self {
+        self.plants.push(Plant {
+            id: plant.id.clone(),
+            name: Some(plant.name.clone()),
+            plant_type: None,
+        });
+        self.topologies.insert(plant.id.clone(), plant);
+        self
+    }
+
+    pub fn with_status(mut self, plant_id: &PlantId, module_id: &ModuleId, status: ModuleStatus) -> Self {
+        self.statuses.insert((plant_id.clone(), module_id.clone()), status);
+        self
+    }
+
+    pub fn with_programs(mut self, plant_id: &PlantId, module_id: &ModuleId, programs: Vec<Program>) -> Self {
+        self.programs.insert((plant_id.clone(), module_id.clone()), programs);
+        self
+    }
+}
+
+type SharedState = Data<Mutex<MockState>>;
+
+pub struct MockSmartherServer {
+    state: Arc<Mutex<MockState>>,
+    base_url: String,
+    handle: ServerHandle,
+}
+
+impl MockSmartherServer {
+    /// Binds the mock on a random local port and serves it in the background.
+    pub async fn start(state: MockState) -> std::io::Result<Self> {
+        let state = Arc::new(Mutex::new(state));
+        let app_state = Data::from(state.clone());
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(app_state.clone())
+                .route("/token", web::post().to(issue_token))
+                .route("/plants", web::get().to(get_plants))
+                .route("/plants/{plant_id}/topology", web::get().to(get_topology))
+                .route("/plants/{plant_id}/subscription", web::post().to(register_webhook))
+                .route("/plants/{plant_id}/subscription/{subscription_id}", web::delete().to(unregister_webhook))
+                .route("/subscription", web::get().to(get_webhooks))
+                .route(THERMOSTAT_PATH, web::get().to(get_status))
+                .route(THERMOSTAT_PATH, web::post().to(set_status))
+                .route(&format!("{THERMOSTAT_PATH}/programlist"), web::get().to(get_programs))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))?;
+
+        let address = server.addrs()[0];
+        let server = server.run();
+        let handle = server.handle();
+        tokio::spawn(server);
+
+        Ok(Self {
+            state,
+            base_url: format!("http://{address}"),
+            handle,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Configuration pointing every endpoint of the client at this server.
+    pub fn config(&self) -> SmartherConfig {
+        SmartherConfig::default()
+            .with_api_url(self.base_url.clone())
+            .with_auth_url(format!("{}/authorize", self.base_url))
+            .with_token_url(format!("{}/token", self.base_url))
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+}
+
+fn lock(state: &SharedState) -> MutexGuard<'_, MockState> {
+    state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Applies the scripted failures and the bearer token check shared by all API endpoints.
+fn check_request(request: &HttpRequest, state: &mut MockState) -> Option<HttpResponse> {
+    if let Some(status) = state.failures.pop_front() {
+        return Some(HttpResponse::build(status).body(status.to_string()));
+    }
+
+    let expected = format!("Bearer {}", state.access_token);
+    let authorized = request.headers().get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value == expected);
+    let subscribed = request.headers().contains_key("Ocp-Apim-Subscription-Key");
+    if !authorized || !subscribed {
+        return Some(HttpResponse::Unauthorized().finish());
+    }
+    None
+}
+
+#[derive(Deserialize)]
+struct TokenRequest {
+    grant_type: String,
+}
+
+async fn issue_token(form: Form<TokenRequest>, state: SharedState) -> HttpResponse {
+    if form.grant_type != "authorization_code" && form.grant_type != "refresh_token" {
+        return HttpResponse::BadRequest().finish();
+    }
+
+    let mut state = lock(&state);
+    state.issued_tokens += 1;
+    state.access_token = format!("mock-access-token-{}", state.issued_tokens);
+    let expires_on = chrono::Utc::now().timestamp() as u64 + state.token_lifetime_secs;
+    HttpResponse::Ok().json(serde_json::json!({
+        "access_token": state.access_token,
+        "refresh_token": format!("mock-refresh-token-{}", state.issued_tokens),
+        "expires_on": expires_on,
+    }))
+}
+
+async fn get_plants(request: HttpRequest, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    HttpResponse::Ok().json(Plants { plants: state.plants.clone() })
+}
+
+async fn get_topology(request: HttpRequest, path: Path<PlantId>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    match state.topologies.get(&path) {
+        Some(plant) => HttpResponse::Ok().json(PlantTopology { plant: plant.clone() }),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn get_status(request: HttpRequest, path: Path<(PlantId, ModuleId)>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    match state.statuses.get(&path.into_inner()) {
+        Some(status) => HttpResponse::Ok().json(status),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+async fn set_status(request: HttpRequest, path: Path<(PlantId, ModuleId)>, body: Json<SetStatusRequest>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    let (plant_id, module_id) = path.into_inner();
+    if !state.statuses.contains_key(&(plant_id.clone(), module_id.clone())) {
+        return HttpResponse::NotFound().finish();
+    }
+    state.status_requests.push((plant_id, module_id, body.into_inner()));
+    HttpResponse::Ok().finish()
+}
+
+async fn get_programs(request: HttpRequest, path: Path<(PlantId, ModuleId)>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    match state.programs.get(&path.into_inner()) {
+        Some(programs) => HttpResponse::Ok().json(ProgramList {
+            chronothermostats: vec![ThermostatPrograms { programs: programs.clone() }],
+        }),
+        None => HttpResponse::NotFound().finish(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SubscriptionRequest {
+    #[serde(rename = "EndPointUrl")]
+    endpoint_url: String,
+}
+
+async fn register_webhook(request: HttpRequest, path: Path<PlantId>, body: Json<SubscriptionRequest>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    state.issued_subscriptions += 1;
+    let subscription = SubscriptionInfo {
+        subscription_id: format!("mock-subscription-{}", state.issued_subscriptions),
+        plant_id: Some(path.into_inner()),
+        endpoint_url: Some(body.into_inner().endpoint_url),
+    };
+    state.subscriptions.push(subscription.clone());
+    HttpResponse::Created().json(subscription)
+}
+
+async fn unregister_webhook(request: HttpRequest, path: Path<(PlantId, String)>, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    let (plant_id, subscription_id) = path.into_inner();
+    let before = state.subscriptions.len();
+    state.subscriptions.retain(|subscription| {
+        subscription.subscription_id != subscription_id || subscription.plant_id.as_ref() != Some(&plant_id)
+    });
+    if state.subscriptions.len() == before {
+        return HttpResponse::NotFound().finish();
+    }
+    HttpResponse::Ok().finish()
+}
+
+async fn get_webhooks(request: HttpRequest, state: SharedState) -> HttpResponse {
+    let mut state = lock(&state);
+    if let Some(response) = check_request(&request, &mut state) {
+        return response;
+    }
+    HttpResponse::Ok().json(&state.subscriptions)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use actix_web::http::StatusCode;
+
+    use super::{MockSmartherServer, MockState};
+    use crate::{
+        model::*,
+        retry::RetryPolicy,
+        AuthorizationGrant, AuthorizationInfo, SmartherApi, SmartherError,
+    };
+
+    fn plant_id() -> PlantId {
+        "b5e48d6f-cbad-2711-e053-27182d0ad74c".into()
+    }
+
+    fn module_id() -> ModuleId {
+        "1ee68d6f-46b7-8f11-e053-27182d0a846a".into()
+    }
+
+    fn mock_state() -> MockState {
+        let status_message_json = std::fs::read_to_string("validation/status_message.json").unwrap();
+        let status: ModuleStatus = serde_json::from_str(&status_message_json).unwrap();
+
+        MockState::default()
+            .with_plant(PlantDetail {
+                id: plant_id(),
+                name: "Home".into(),
+                modules: vec![Module {
+                    device: "chronothermostat".into(),
+                    name: "Living room".into(),
+                    id: module_id(),
+                    capabilities: None,
+                }],
+            })
+            .with_status(&plant_id(), &module_id(), status)
+            .with_programs(&plant_id(), &module_id(), vec![Program { number: 1, name: "Winter".into() }])
+    }
+
+    fn auth_info(access_token: &str, expires_on: u64) -> AuthorizationInfo {
+        AuthorizationInfo {
+            grant: AuthorizationGrant::OAuthToken {
+                access_token: access_token.into(),
+                refresh_token: "refresh".into(),
+                expires_on,
+            },
+            client_id: "test".into(),
+            client_secret: "secret".into(),
+            subscription_key: "sub".into(),
+        }
+    }
+
+    fn fast_retries() -> RetryPolicy {
+        RetryPolicy {
+            initial_backoff: Duration::from_millis(1),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn full_api_flow() {
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        let plants = client.get_plants().await.unwrap();
+        assert_eq!(plants.plants[0].id, plant_id());
+
+        let topology = client.get_topology(&plant_id()).await.unwrap();
+        assert_eq!(topology.plant.modules[0].id, module_id());
+
+        let status = client.get_device_status(&plant_id(), &module_id()).await.unwrap();
+        assert_eq!(status.chronothermostats.len(), 1);
+
+        let programs = client.get_program_list(&plant_id(), &module_id()).await.unwrap();
+        assert_eq!(programs[0].name, "Winter");
+
+        client.set_device_status(&plant_id(), &module_id(), SetStatusRequest::off(ThermostatFunction::Heating)).await.unwrap();
+        assert_eq!(server.state().status_requests.len(), 1);
+
+        let subscription = client.register_webhook(&plant_id(), "https://example.com/notify".into()).await.unwrap();
+        assert_eq!(client.get_webhooks().await.unwrap(), vec![subscription.clone()]);
+        client.unregister_webhook(&plant_id(), &subscription.subscription_id).await.unwrap();
+        assert!(client.get_webhooks().await.unwrap().is_empty());
+
+        assert!(matches!(
+            client.get_topology(&"unknown".into()).await,
+            Err(SmartherError::Http { status, .. }) if status == StatusCode::NOT_FOUND
+        ));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn discovery_walks_all_plants() {
+        let second_plant = PlantDetail {
+            id: "second-plant".into(),
+            name: "Office".into(),
+            modules: vec![
+                Module { device: "chronothermostat".into(), name: "Desk".into(), id: "desk".into(), capabilities: None },
+                Module { device: "chronothermostat".into(), name: "Hall".into(), id: "hall".into(), capabilities: None },
+            ],
+        };
+        let server = MockSmartherServer::start(mock_state().with_plant(second_plant)).await.unwrap();
+        let client = SmartherApi::with_config(server.config().with_max_concurrent_requests(1)).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        let modules = client.discover().await.unwrap();
+        let found: Vec<_> = modules.iter()
+            .map(|discovered| (discovered.plant.id.as_str(), discovered.module.id.as_str()))
+            .collect();
+        assert_eq!(found, vec![
+            (plant_id().as_str(), module_id().as_str()),
+            ("second-plant", "desk"),
+            ("second-plant", "hall"),
+        ]);
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn plant_status_reports_failures_per_module() {
+        let mut state = mock_state();
+        state.topologies.get_mut(&plant_id()).unwrap().modules.push(Module {
+            device: "chronothermostat".into(),
+            name: "Unreachable".into(),
+            id: "unreachable".into(),
+            capabilities: None,
+        });
+        let server = MockSmartherServer::start(state).await.unwrap();
+        let client = SmartherApi::with_config(server.config().with_max_concurrent_requests(1)).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        let results = client.get_plant_status(&plant_id()).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].module.id, module_id());
+        assert!(results[0].status.is_ok());
+        assert!(matches!(
+            results[1].status,
+            Err(SmartherError::Http { status, .. }) if status == StatusCode::NOT_FOUND
+        ));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn watch_yields_status_changes() {
+        use futures::StreamExt;
+        use crate::events::ThermostatEvent;
+
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        let events = client.watch(plant_id(), module_id(), Duration::from_millis(10));
+        futures::pin_mut!(events);
+        for _ in 0..5 {
+            events.next().await.unwrap().unwrap();
+        }
+
+        server.state().statuses.get_mut(&(plant_id(), module_id())).unwrap()
+            .chronothermostats[0].mode = ThermostatMode::Off;
+        let event = events.next().await.unwrap().unwrap();
+        assert_eq!(event.module_id, module_id());
+        assert_eq!(event.event, ThermostatEvent::ModeChanged {
+            previous: Some(ThermostatMode::Automatic),
+            current: ThermostatMode::Off,
+        });
+
+        server.state().failures.push_back(StatusCode::NOT_FOUND);
+        assert!(events.next().await.unwrap().is_err());
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn thermostat_handle_caches_status() {
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+        let thermostat = client.thermostat(plant_id(), module_id());
+
+        assert!(thermostat.cached_status().is_none());
+        let status = thermostat.status().await.unwrap();
+        assert_eq!(status.mode, ThermostatMode::Automatic);
+        assert_eq!(thermostat.cached_status(), Some(status));
+
+        assert!(matches!(thermostat.set_manual(Measurement::Celsius(19.37)).await, Err(SmartherError::Validation(_))));
+        thermostat.set_manual(Measurement::Fahrenheit(69.8)).await.unwrap();
+        assert!(thermostat.cached_status().is_none());
+        thermostat.boost(chrono::Duration::minutes(30)).await.unwrap();
+        thermostat.off().await.unwrap();
+        thermostat.auto(1).await.unwrap();
+
+        let modes: Vec<_> = server.state().status_requests.iter()
+            .map(|(_, _, request)| request.mode().clone())
+            .collect();
+        assert_eq!(modes, vec![ThermostatMode::Manual, ThermostatMode::Boost, ThermostatMode::Off, ThermostatMode::Automatic]);
+        assert_eq!(server.state().status_requests[0].2.set_point(), Some(&Measurement::Celsius(21.0)));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn webhooks_are_reconciled() {
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+        let other_plant = PlantId::from("other-plant");
+        let old_endpoint = client.register_webhook(&plant_id(), "https://old.example.com/notify".into()).await.unwrap();
+        let other_endpoint = client.register_webhook(&other_plant, "https://other.example.com/notify".into()).await.unwrap();
+        let without_plant = SubscriptionInfo {
+            subscription_id: "without-plant".into(),
+            plant_id: None,
+            endpoint_url: Some("https://example.com/notify".into()),
+        };
+        server.state().subscriptions.push(without_plant.clone());
+
+        let desired = [(plant_id(), "https://example.com/notify")];
+        let reconciliation = client.reconcile_webhooks(desired.clone()).await.unwrap();
+        assert!(reconciliation.kept.is_empty());
+        assert_eq!(reconciliation.created.len(), 1);
+        assert_eq!(reconciliation.created[0].endpoint_url.as_deref(), Some("https://example.com/notify"));
+        assert_eq!(reconciliation.removed, vec![old_endpoint]);
+        assert_eq!(reconciliation.ignored, vec![other_endpoint.clone(), without_plant.clone()]);
+
+        let duplicate = client.register_webhook(&plant_id(), "https://example.com/notify".into()).await.unwrap();
+        let reconciliation = client.reconcile_webhooks(desired.clone()).await.unwrap();
+        assert_eq!(reconciliation.kept.len(), 1);
+        assert!(reconciliation.created.is_empty());
+        assert_eq!(reconciliation.removed, vec![duplicate]);
+
+        let reconciliation = client.reconcile_webhooks(desired.clone()).await.unwrap();
+        assert!(reconciliation.is_unchanged());
+        let subscriptions = client.get_webhooks().await.unwrap();
+        assert_eq!(subscriptions.len(), 3);
+        assert!(subscriptions.contains(&other_endpoint) && subscriptions.contains(&without_plant));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn expired_and_revoked_tokens_are_refreshed() {
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("expired", 0))
+            .unwrap();
+
+        client.get_plants().await.unwrap();
+        assert_eq!(server.state().issued_tokens, 1);
+
+        server.state().access_token = "revoked".into();
+        client.get_plants().await.unwrap();
+        assert_eq!(server.state().issued_tokens, 2);
+        assert_eq!(client.authorization_info().grant.request_token().unwrap(), "mock-access-token-2");
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn set_points_follow_module_limits() {
+        let mut state = mock_state();
+        let module = &mut state.topologies.get_mut(&plant_id()).unwrap().modules[0];
+        module.capabilities = Some(vec![ModuleCapability {
+            max_set_point: Some(45.0),
+            set_point_step: Some(0.1),
+            ..Default::default()
+        }]);
+        let module = module.clone();
+        let server = MockSmartherServer::start(state).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        for set_point in [Measurement::Celsius(44.0), Measurement::Celsius(21.3)] {
+            let request = SetStatusRequest::manual(ThermostatFunction::Heating, set_point, None);
+            assert!(request.validate().is_err());
+            assert!(request.validate_for(&module).is_ok());
+            client.set_device_status(&plant_id(), &module_id(), request).await.unwrap();
+        }
+        assert_eq!(server.state().status_requests.len(), 2);
+
+        let not_a_temperature = SetStatusRequest::manual(ThermostatFunction::Heating, Measurement::Percentage(50.0), None);
+        assert!(matches!(
+            client.set_device_status(&plant_id(), &module_id(), not_a_temperature).await,
+            Err(SmartherError::Validation(_))
+        ));
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn refreshed_tokens_survive_store_failures() {
+        use futures::future::BoxFuture;
+        use crate::store::TokenStore;
+
+        struct FailingTokenStore;
+
+        impl TokenStore for FailingTokenStore {
+            fn load(&self) -> BoxFuture<'_, Result<Option<AuthorizationInfo>, SmartherError>> {
+                Box::pin(async { Ok(None) })
+            }
+
+            fn save<'a>(&'a self, _: &'a AuthorizationInfo) -> BoxFuture<'a, Result<(), SmartherError>> {
+                Box::pin(async { Err(std::io::Error::other("disk full").into()) })
+            }
+        }
+
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_token_store(FailingTokenStore);
+        let refreshed = client.refresh_token(&auth_info("expired", 0)).await.unwrap();
+        assert_eq!(refreshed.grant.request_token().unwrap(), "mock-access-token-1");
+
+        let client = client.with_authorization(auth_info("expired", 0)).unwrap();
+        client.get_plants().await.unwrap();
+        assert_eq!(client.authorization_info().grant.request_token().unwrap(), "mock-access-token-2");
+
+        server.stop().await;
+    }
+
+    #[tokio::test]
+    async fn rate_limited_requests_are_retried() {
+        let server = MockSmartherServer::start(mock_state()).await.unwrap();
+        let client = SmartherApi::with_config(server.config()).unwrap()
+            .with_retry_policy(fast_retries())
+            .with_authorization(auth_info("mock-access-token", u64::MAX))
+            .unwrap();
+
+        server.state().failures.extend([StatusCode::TOO_MANY_REQUESTS, StatusCode::SERVICE_UNAVAILABLE]);
+        client.get_plants().await.unwrap();
+
+        server.state().failures.extend([StatusCode::TOO_MANY_REQUESTS; 4]);
+        assert!(matches!(client.get_plants().await, Err(SmartherError::RateLimited { .. })));
+
+        server.state().failures.push_back(StatusCode::TOO_MANY_REQUESTS);
+        assert!(matches!(
+            client.set_device_status(&plant_id(), &module_id(), SetStatusRequest::off(ThermostatFunction::Heating)).await,
+            Err(SmartherError::RateLimited { .. })
+        ));
+
+        server.stop().await;
+    }
+}
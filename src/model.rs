@@ -1,8 +1,99 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap};
 
 use chrono::{DateTime, Utc};
 use serde_aux::prelude::*;
 
+use crate::SmartherError;
+
+/// Prefix the notification subjects put in front of plant ids.
+const CLOUD_ID_PREFIX: &str = "bs_cloud_";
+
+/// Declares a string identifier newtype, every conversion from a string goes through its `new`.
+macro_rules! id_type {
+    ($(#[$meta:meta])* pub struct $name:ident;) => {
+        $(#[$meta])*
+        #[derive(Debug, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord, Clone)]
+        #[serde(transparent)]
+        pub struct $name(String);
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = std::convert::Infallible;
+
+            fn from_str(id: &str) -> Result<Self, Self::Err> {
+                Ok($name::new(id))
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(id: &str) -> Self {
+                $name::new(id)
+            }
+        }
+
+        impl From<String> for $name {
+            fn from(id: String) -> Self {
+                $name::new(id)
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <String as serde::Deserialize>::deserialize(deserializer).map($name::new)
+            }
+        }
+    };
+}
+
+id_type! {
+    /// Plant identifier, accepting also the `bs_cloud_` prefixed form used in C2C notifications.
+    pub struct PlantId;
+}
+
+id_type! {
+    pub struct ModuleId;
+}
+
+impl ModuleId {
+    pub fn new(id: impl Into<String>) -> Self {
+        ModuleId(id.into())
+    }
+}
+
+impl PlantId {
+    pub fn new(id: impl Into<String>) -> Self {
+        let id = id.into();
+        match id.strip_prefix(CLOUD_ID_PREFIX) {
+            Some(stripped) => PlantId(stripped.to_string()),
+            None => PlantId(id),
+        }
+    }
+
+    /// Extracts the plant id from a notification subject such as `///bs_cloud_<plant id>//`.
+    pub fn from_subject(subject: &str) -> Option<PlantId> {
+        subject.split('/')
+            .find(|segment| segment.starts_with(CLOUD_ID_PREFIX))
+            .map(PlantId::new)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Hash, PartialOrd, Clone)]
 pub struct Plants {
     pub plants: Vec<Plant>,
@@ -10,7 +101,7 @@ pub struct Plants {
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Hash, PartialOrd, Clone)]
 pub struct Plant {
-    pub id: String,
+    pub id: PlantId,
     pub name: Option<String>,
     #[serde(rename = "type")]
     pub plant_type: Option<String>,
@@ -23,7 +114,7 @@ pub struct PlantTopology {
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct PlantDetail {
-    pub id: String,
+    pub id: PlantId,
     pub name: String,
     pub modules: Vec<Module>,
 }
@@ -32,16 +123,91 @@ pub struct PlantDetail {
 pub struct Module {
     pub device: String,
     pub name: String,
-    pub id: String,
+    pub id: ModuleId,
     pub capabilities: Option<Vec<ModuleCapability>>,
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
-pub struct ModuleCapability {
-    capability: Option<String>,
+impl Module {
+    fn capabilities(&self) -> impl Iterator<Item = &ModuleCapability> {
+        self.capabilities.iter().flatten()
+    }
+
+    /// Limits advertised by the module capabilities, falling back to the defaults.
+    pub fn set_point_limits(&self) -> SetPointLimits {
+        let mut limits = SetPointLimits::default();
+        for capability in self.capabilities() {
+            limits.min = capability.min_set_point.unwrap_or(limits.min);
+            limits.max = capability.max_set_point.unwrap_or(limits.max);
+            limits.step = capability.set_point_step.unwrap_or(limits.step);
+        }
+        limits
+    }
+
+    /// Whether the module accepts the given mode, modules without declared modes are assumed to accept every mode.
+    pub fn supports(&self, mode: &ThermostatMode) -> bool {
+        let mut declared = self.capabilities()
+            .filter_map(|capability| capability.modes.as_ref())
+            .peekable();
+        declared.peek().is_none() || declared.flatten().any(|supported| supported == mode)
+    }
+
+    /// Whether the module accepts the given function, modules without declared functions are assumed to accept every function.
+    pub fn supports_function(&self, function: &ThermostatFunction) -> bool {
+        let mut declared = self.capabilities()
+            .filter_map(|capability| capability.functions.as_ref())
+            .peekable();
+        declared.peek().is_none() || declared.flatten().any(|supported| supported == function)
+    }
 
+    pub fn can_do(&self, feature: &str) -> bool {
+        self.capabilities().any(|capability| capability.can_do(feature))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleCapability {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capability: Option<String>,
+    /// Features the module can perform, sent either as a list of names or as a map of boolean flags
+    #[serde(default, deserialize_with = "deserialize_can_do", skip_serializing_if = "Vec::is_empty")]
+    pub can_do: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modes: Option<Vec<ThermostatMode>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub functions: Option<Vec<ThermostatFunction>>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string", skip_serializing_if = "Option::is_none")]
+    pub min_set_point: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string", skip_serializing_if = "Option::is_none")]
+    pub max_set_point: Option<f32>,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string", skip_serializing_if = "Option::is_none")]
+    pub set_point_step: Option<f32>,
+    /// Descriptors not modeled yet
     #[serde(flatten)]
-    pub can_do: Option<HashMap<String, serde_json::Value>>,
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ModuleCapability {
+    pub fn can_do(&self, feature: &str) -> bool {
+        self.can_do.iter().any(|supported| supported.eq_ignore_ascii_case(feature))
+    }
+}
+
+fn deserialize_can_do<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CanDo {
+        List(Vec<String>),
+        Flags(HashMap<String, bool>),
+    }
+
+    Ok(match <Option<CanDo> as serde::Deserialize>::deserialize(deserializer)? {
+        Some(CanDo::List(features)) => features,
+        Some(CanDo::Flags(flags)) => flags.into_iter()
+            .filter_map(|(feature, enabled)| enabled.then_some(feature))
+            .collect(),
+        None => Vec::new(),
+    })
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -62,8 +228,37 @@ pub struct ThermostatStatus {
     pub time: DateTime<Utc>,
     pub thermometer: Option<Instrument>,
     pub hygrometer: Option<Instrument>,
+    pub online: Option<bool>,
     pub sender: Option<SenderInfo>,
     pub receiver: Option<ReceiverInfo>,
+    /// Fields not modeled yet, kept to avoid losing data on API changes
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl ThermostatStatus {
+    /// Builds a manual request for the given temperature, expressed in the unit the thermostat is configured with.
+    ///
+    /// A temperature converted to another unit is rounded to the step `limits` give for that unit, a temperature
+    /// already in the right unit is kept as is and left to the validation.
+    pub fn manual_request(&self, temperature: Measurement, until: Option<DateTime<Utc>>, limits: &SetPointLimits) -> Result<SetStatusRequest, SmartherError> {
+        let unit = self.temperature_format.unwrap_or_else(|| temperature.unit());
+        let invalid_temperature = || SmartherError::Validation(format!("{temperature:?} is not a valid temperature"));
+        let set_point = temperature.in_unit(&unit)
+            .filter(Measurement::is_temperature)
+            .ok_or_else(invalid_temperature)?;
+        if unit == temperature.unit() {
+            return Ok(SetStatusRequest::manual(self.function.clone(), set_point, until));
+        }
+
+        let step = limits.step_for(&unit).ok_or_else(invalid_temperature)?;
+        let rounded = (set_point.value() / step).round() * step;
+        let set_point = match unit {
+            MeasurementUnit::Fahrenheit => Measurement::Fahrenheit(rounded),
+            _ => Measurement::Celsius(rounded),
+        };
+        Ok(SetStatusRequest::manual(self.function.clone(), set_point, until))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -71,6 +266,36 @@ pub struct ProgramIdentifier {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub number: u32,
 }
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct ProgramList {
+    pub chronothermostats: Vec<ThermostatPrograms>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct ThermostatPrograms {
+    pub programs: Vec<Program>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+pub struct Program {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub number: u32,
+    pub name: String,
+}
+
+impl From<u32> for ProgramIdentifier {
+    fn from(number: u32) -> Self {
+        ProgramIdentifier { number }
+    }
+}
+
+impl From<&Program> for ProgramIdentifier {
+    fn from(program: &Program) -> Self {
+        ProgramIdentifier { number: program.number }
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SenderInfo {
@@ -85,28 +310,77 @@ pub struct ReceiverInfo {
     pub oid: Option<Vec<String>>
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum ThermostatFunction {
-    Heating,
-    Cooling,
+/// Declares a string enum that keeps values it doesn't know in an `Unknown` variant,
+/// so that a single new value sent by the API doesn't break the whole response.
+macro_rules! open_enum {
+    (pub enum $name:ident { $($variant:ident => $value:literal),* $(,)? }) => {
+        #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+        pub enum $name {
+            $($variant,)*
+            Unknown(String),
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(value) => value,
+                }
+            }
+        }
+
+        impl From<&str> for $name {
+            fn from(value: &str) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    _ => $name::Unknown(value.to_string()),
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl serde::Serialize for $name {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let value = <String as serde::Deserialize>::deserialize(deserializer)?;
+                Ok($name::from(value.as_str()))
+            }
+        }
+    };
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum ThermostatMode {
-    Automatic,
-    Manual,
-    Boost,
-    Off,
-    Protection,
+open_enum! {
+    pub enum ThermostatFunction {
+        Heating => "HEATING",
+        Cooling => "COOLING",
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
-#[serde(rename_all = "UPPERCASE")]
-pub enum LoadState {
-    Active,
-    Inactive,
+open_enum! {
+    pub enum ThermostatMode {
+        Automatic => "AUTOMATIC",
+        Manual => "MANUAL",
+        Boost => "BOOST",
+        Off => "OFF",
+        Protection => "PROTECTION",
+    }
+}
+
+open_enum! {
+    pub enum LoadState {
+        Active => "ACTIVE",
+        Inactive => "INACTIVE",
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
@@ -118,8 +392,8 @@ impl Instrument {
     pub fn last_measurement(&self) -> Option<&TimedMeasurement> {
         self.measures.as_ref().and_then(|measurements| {
             let mut sorted_set: Vec<&TimedMeasurement> = measurements.iter().collect();
-            sorted_set.sort_by(|a,b| a.time_stamp.cmp(&b.time_stamp));
-            return sorted_set.last().copied();
+            sorted_set.sort_by_key(|a| a.time_stamp);
+            sorted_set.last().copied()
         })
     }
 }
@@ -146,7 +420,66 @@ pub enum Measurement {
     Percentage(f32),
 }
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
+impl Measurement {
+    pub fn value(&self) -> f32 {
+        match self {
+            Measurement::Celsius(value) | Measurement::Fahrenheit(value) | Measurement::Percentage(value) => *value,
+        }
+    }
+
+    pub fn unit(&self) -> MeasurementUnit {
+        match self {
+            Measurement::Celsius(_) => MeasurementUnit::Celsius,
+            Measurement::Fahrenheit(_) => MeasurementUnit::Fahrenheit,
+            Measurement::Percentage(_) => MeasurementUnit::Percentage,
+        }
+    }
+
+    pub fn is_temperature(&self) -> bool {
+        !matches!(self, Measurement::Percentage(_))
+    }
+
+    /// Returns `None` for percentages, which can't be converted to a temperature.
+    pub fn to_celsius(&self) -> Option<Measurement> {
+        match self {
+            Measurement::Celsius(value) => Some(Measurement::Celsius(*value)),
+            Measurement::Fahrenheit(value) => Some(Measurement::Celsius(((*value as f64 - 32.0) * 5.0 / 9.0) as f32)),
+            Measurement::Percentage(_) => None,
+        }
+    }
+
+    /// Returns `None` for percentages, which can't be converted to a temperature.
+    pub fn to_fahrenheit(&self) -> Option<Measurement> {
+        match self {
+            Measurement::Celsius(value) => Some(Measurement::Fahrenheit((*value as f64 * 9.0 / 5.0 + 32.0) as f32)),
+            Measurement::Fahrenheit(value) => Some(Measurement::Fahrenheit(*value)),
+            Measurement::Percentage(_) => None,
+        }
+    }
+
+    /// Converts between temperature units, percentages only convert to themselves.
+    pub fn in_unit(&self, unit: &MeasurementUnit) -> Option<Measurement> {
+        match (self, unit) {
+            (Measurement::Percentage(value), MeasurementUnit::Percentage) => Some(Measurement::Percentage(*value)),
+            (_, MeasurementUnit::Celsius) => self.to_celsius(),
+            (_, MeasurementUnit::Fahrenheit) => self.to_fahrenheit(),
+            (_, MeasurementUnit::Percentage) => None,
+        }
+    }
+
+    /// Compares measurements of the same kind regardless of their unit.
+    /// Values are rounded to hundredths of a degree, so that conversion noise doesn't affect equality.
+    pub fn compare(&self, other: &Measurement) -> Option<Ordering> {
+        let (lhs, rhs) = match (self, other) {
+            (Measurement::Percentage(lhs), Measurement::Percentage(rhs)) => (*lhs, *rhs),
+            _ => (self.to_celsius()?.value(), other.to_celsius()?.value()),
+        };
+        let round = |value: f32| (value as f64 * 100.0).round() as i64;
+        Some(round(lhs).cmp(&round(rhs)))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum MeasurementUnit {
     #[serde(rename = "C")]
     Celsius,
@@ -158,51 +491,230 @@ pub enum MeasurementUnit {
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct PlantMinimalDetails {
-    pub id: String,
+    pub id: PlantId,
     pub module: ModuleMinimalDetail,
 }
 
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 pub struct ModuleMinimalDetail {
-    pub id: String,
+    pub id: ModuleId,
 }
 
+/// Status change sent through `set_device_status`, built with one constructor per mode.
+///
+/// The constructors fill in the fields each mode needs, but don't check their values: a set point that is not
+/// a temperature or out of limits, or an empty program list, is only caught by [`SetStatusRequest::validate`].
+/// The same applies to deserialized requests. `set_device_status` checks the fields of every request before
+/// sending it, the set point limits depend on the module and are left to [`SetStatusRequest::validate_for`].
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SetStatusRequest {
-    pub function: ThermostatFunction,
-    pub mode: ThermostatMode,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub set_point: Option<Measurement>,
+    function: ThermostatFunction,
+    mode: ThermostatMode,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub programs: Option<Vec<ProgramIdentifier>>,
+    set_point: Option<Measurement>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub activation_time: Option<String>,
+    programs: Option<Vec<ProgramIdentifier>>,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "activation_time_format")]
+    activation_time: Option<DateTime<Utc>>,
 }
 
 impl SetStatusRequest {
-    pub fn validate(&self) -> bool {
+    fn new(function: ThermostatFunction, mode: ThermostatMode) -> Self {
+        SetStatusRequest {
+            function,
+            mode,
+            set_point: None,
+            programs: None,
+            activation_time: None,
+        }
+    }
+
+    /// Holds the given set point, indefinitely or until the given time.
+    pub fn manual(function: ThermostatFunction, set_point: Measurement, until: Option<DateTime<Utc>>) -> Self {
+        SetStatusRequest {
+            set_point: Some(set_point),
+            activation_time: until,
+            ..Self::new(function, ThermostatMode::Manual)
+        }
+    }
+
+    /// Forces the load on for the given duration, starting now.
+    pub fn boost(function: ThermostatFunction, duration: chrono::Duration) -> Self {
+        SetStatusRequest {
+            activation_time: Some(Utc::now() + duration),
+            ..Self::new(function, ThermostatMode::Boost)
+        }
+    }
+
+    /// Follows the given programs, at least one is required.
+    pub fn automatic<P: Into<ProgramIdentifier>>(function: ThermostatFunction, programs: impl IntoIterator<Item = P>) -> Self {
+        SetStatusRequest {
+            programs: Some(programs.into_iter().map(Into::into).collect()),
+            ..Self::new(function, ThermostatMode::Automatic)
+        }
+    }
+
+    pub fn off(function: ThermostatFunction) -> Self {
+        Self::new(function, ThermostatMode::Off)
+    }
+
+    pub fn protection(function: ThermostatFunction) -> Self {
+        Self::new(function, ThermostatMode::Protection)
+    }
+
+    pub fn function(&self) -> &ThermostatFunction {
+        &self.function
+    }
+
+    pub fn mode(&self) -> &ThermostatMode {
+        &self.mode
+    }
+
+    pub fn set_point(&self) -> Option<&Measurement> {
+        self.set_point.as_ref()
+    }
+
+    pub fn programs(&self) -> Option<&[ProgramIdentifier]> {
+        self.programs.as_deref()
+    }
+
+    pub fn activation_time(&self) -> Option<DateTime<Utc>> {
+        self.activation_time
+    }
+
+    /// Checks the request against the default set point limits, see [`SetStatusRequest::validate_for`].
+    pub fn validate(&self) -> Result<(), SmartherError> {
+        self.validate_with_limits(&SetPointLimits::default())
+    }
+
+    /// Checks the request against the set point limits advertised by the module capabilities.
+    pub fn validate_for(&self, module: &Module) -> Result<(), SmartherError> {
+        self.validate_with_limits(&module.set_point_limits())
+    }
+
+    pub fn validate_with_limits(&self, limits: &SetPointLimits) -> Result<(), SmartherError> {
+        self.validate_fields()?;
+        match &self.set_point {
+            Some(set_point) if self.mode == ThermostatMode::Manual => limits.check(set_point),
+            _ => Ok(())
+        }
+    }
+
+    /// Checks the fields required by the mode, without checking the set point against any limits.
+    pub fn validate_fields(&self) -> Result<(), SmartherError> {
+        let now = Utc::now();
+        let invalid = |reason: &str| Err(SmartherError::Validation(reason.into()));
         match self.mode {
             ThermostatMode::Manual => {
-                self.set_point.is_some()
+                let Some(set_point) = &self.set_point else {
+                    return invalid("Manual mode requires a set point");
+                };
+                if !set_point.is_temperature() {
+                    return invalid("Manual mode requires a temperature set point");
+                }
+                match self.activation_time {
+                    Some(time) if time <= now => invalid("Manual mode end time must be in the future"),
+                    _ => Ok(())
+                }
             }
             ThermostatMode::Boost => {
-                self.activation_time.is_some()
+                match self.activation_time {
+                    None => invalid("Boost mode requires an end time"),
+                    Some(time) if time <= now => invalid("Boost mode end time must be in the future"),
+                    _ => Ok(())
+                }
             },
-            ThermostatMode::Automatic => {
-                self.programs.is_some()
+            ThermostatMode::Automatic if self.programs.as_ref().is_none_or(Vec::is_empty) => {
+                invalid("Automatic mode requires a program")
             },
-            _ => true
+            _ => Ok(())
         }
     }
 }
 
+/// Range accepted for manual set points expressed in Celsius, along with the granularity of each temperature unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SetPointLimits {
+    pub min: f32,
+    pub max: f32,
+    /// Granularity of the set points expressed in Celsius
+    pub step: f32,
+    /// Granularity of the set points expressed in Fahrenheit
+    pub step_fahrenheit: f32,
+}
+
+impl Default for SetPointLimits {
+    fn default() -> Self {
+        Self {
+            min: 3.0,
+            max: 40.0,
+            step: 0.5,
+            step_fahrenheit: 1.0,
+        }
+    }
+}
+
+impl SetPointLimits {
+    /// Granularity of the set points expressed in `unit`, `None` for units that are not temperatures.
+    pub fn step_for(&self, unit: &MeasurementUnit) -> Option<f32> {
+        match unit {
+            MeasurementUnit::Celsius => Some(self.step),
+            MeasurementUnit::Fahrenheit => Some(self.step_fahrenheit),
+            MeasurementUnit::Percentage => None,
+        }
+    }
+
+    pub fn check(&self, set_point: &Measurement) -> Result<(), SmartherError> {
+        let not_a_temperature = || SmartherError::Validation(format!("Set point {set_point:?} is not a temperature"));
+        let celsius = set_point.to_celsius().ok_or_else(not_a_temperature)?.value();
+
+        // Tolerance for values converted from Fahrenheit
+        const TOLERANCE: f32 = 0.05;
+        if celsius < self.min - TOLERANCE || celsius > self.max + TOLERANCE {
+            return Err(SmartherError::Validation(format!(
+                "Set point {celsius:.1}°C is outside of the supported range {:.1}°C - {:.1}°C", self.min, self.max
+            )));
+        }
+
+        // The step applies to the unit the set point is expressed in, Celsius steps start from the minimum
+        let unit = set_point.unit();
+        let step = self.step_for(&unit).ok_or_else(not_a_temperature)?;
+        let origin = if unit == MeasurementUnit::Celsius { self.min } else { 0.0 };
+        let steps = (set_point.value() - origin) / step;
+        if (steps - steps.round()).abs() * step > TOLERANCE {
+            let symbol = if unit == MeasurementUnit::Celsius { "°C" } else { "°F" };
+            return Err(SmartherError::Validation(format!(
+                "Set point {:.2}{symbol} is not a multiple of {step}{symbol}", set_point.value()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The API expects activation times without fractional seconds, e.g. `2021-01-05T17:53:22Z`.
+mod activation_time_format {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&time.format("%FT%TZ").to_string()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        Option::<DateTime<Utc>>::deserialize(deserializer)
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SubscriptionInfo {
     pub subscription_id: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub plant_id: Option<String>,
+    pub plant_id: Option<PlantId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "EndPointUrl")]
     pub endpoint_url: Option<String>
@@ -220,4 +732,26 @@ pub struct C2CEvent {
     pub subject_elements: Option<HashMap<String, serde_json::Value>>
 }
 
+impl C2CEvent {
+    /// Plant the event refers to, parsed from the `bs_cloud_` prefixed subject or from the subject elements.
+    pub fn plant_id(&self) -> Option<PlantId> {
+        PlantId::from_subject(&self.subject).or_else(|| {
+            self.subject_elements.as_ref()?
+                .get("subjectElements")?
+                .get("plantId")?
+                .as_str()
+                .map(PlantId::new)
+        })
+    }
+
+    /// Thermostat statuses carried by the event with the plant and module they belong to, taken from their sender.
+    /// Statuses without a sender can't be attributed to a module and are skipped.
+    pub fn module_statuses(&self) -> impl Iterator<Item = (&PlantId, &ModuleId, &ThermostatStatus)> {
+        self.data.chronothermostats.iter().filter_map(|status| {
+            let sender_plant = status.sender.as_ref()?.plant.as_ref()?;
+            Some((&sender_plant.id, &sender_plant.module.id, status))
+        })
+    }
+}
+
 pub type C2CEvents = Vec<C2CEvent>;
\ No newline at end of file
@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+
+/// Controls how requests failing with rate limits, gateway errors or connection issues are retried.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Only requests using these methods are retried, defaults to idempotent `GET`s.
+    pub retry_methods: Vec<Method>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            retry_methods: vec![Method::GET],
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn allows(&self, method: &Method) -> bool {
+        self.retry_methods.contains(method)
+    }
+
+    /// Exponential backoff, unless the server asked for a specific delay through `Retry-After`.
+    /// Returns `None` when the requested delay exceeds `max_backoff`, to let the caller see the rate limit.
+    pub(crate) fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(self.initial_backoff
+                .saturating_mul(2u32.saturating_pow(attempt))
+                .min(self.max_backoff)),
+        }
+    }
+}
+
+pub(crate) fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(status,
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT)
+}
+
+pub(crate) fn is_retryable_error(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect()
+}
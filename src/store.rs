@@ -0,0 +1,100 @@
+use std::{path::PathBuf, sync::RwLock};
+
+use futures::future::BoxFuture;
+use tokio::io::AsyncWriteExt;
+
+use crate::{AuthorizationInfo, SmartherError};
+
+/// Persistence for [`AuthorizationInfo`], called by the client every time a token gets refreshed.
+pub trait TokenStore: Send + Sync {
+    fn load(&self) -> BoxFuture<'_, Result<Option<AuthorizationInfo>, SmartherError>>;
+    fn save<'a>(&'a self, auth_info: &'a AuthorizationInfo) -> BoxFuture<'a, Result<(), SmartherError>>;
+}
+
+/// Stores the authorization as pretty printed JSON, the same format used by the cli example.
+#[derive(Debug, Clone)]
+pub struct JsonFileTokenStore {
+    path: PathBuf,
+}
+
+impl JsonFileTokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Sibling of the token file the new content is written to before replacing it.
+    fn temp_path(&self) -> PathBuf {
+        let mut file_name = self.path.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".tmp");
+        self.path.with_file_name(file_name)
+    }
+}
+
+impl TokenStore for JsonFileTokenStore {
+    fn load(&self) -> BoxFuture<'_, Result<Option<AuthorizationInfo>, SmartherError>> {
+        Box::pin(async move {
+            let payload = match tokio::fs::read_to_string(&self.path).await {
+                Ok(payload) => payload,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+
+            serde_json::from_str(&payload)
+                .map(Some)
+                .map_err(|source| SmartherError::Deserialization { source, payload })
+        })
+    }
+
+    fn save<'a>(&'a self, auth_info: &'a AuthorizationInfo) -> BoxFuture<'a, Result<(), SmartherError>> {
+        Box::pin(async move {
+            let content = serde_json::to_string_pretty(auth_info).map_err(std::io::Error::from)?;
+
+            // Replaced with a rename, so a failed write never leaves a truncated token file behind
+            let temp_path = self.temp_path();
+            // A leftover from an interrupted save may not have the right permissions
+            if let Err(err) = tokio::fs::remove_file(&temp_path).await {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    return Err(err.into());
+                }
+            }
+            let mut options = tokio::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            // The file holds the client secret and the refresh token
+            #[cfg(unix)]
+            options.mode(0o600);
+            let mut file = options.open(&temp_path).await?;
+            file.write_all(content.as_bytes()).await?;
+            file.sync_all().await?;
+            drop(file);
+
+            tokio::fs::rename(&temp_path, &self.path).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Keeps the authorization in memory only, useful for tests and short lived processes.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    auth_info: RwLock<Option<AuthorizationInfo>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new(auth_info: Option<AuthorizationInfo>) -> Self {
+        Self { auth_info: RwLock::new(auth_info) }
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> BoxFuture<'_, Result<Option<AuthorizationInfo>, SmartherError>> {
+        let auth_info = self.auth_info.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        Box::pin(async move { Ok(auth_info) })
+    }
+
+    fn save<'a>(&'a self, auth_info: &'a AuthorizationInfo) -> BoxFuture<'a, Result<(), SmartherError>> {
+        *self.auth_info.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(auth_info.clone());
+        Box::pin(async move { Ok(()) })
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    model::{PlantId, SubscriptionInfo},
+    states::Authorized,
+    SmartherApi, SmartherError,
+};
+
+/// Changes made by [`SmartherApi::reconcile_webhooks`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WebhookReconciliation {
+    /// Subscriptions already pointing at the desired endpoint
+    pub kept: Vec<SubscriptionInfo>,
+    pub created: Vec<SubscriptionInfo>,
+    /// Subscriptions pointing elsewhere, or duplicates of a kept one
+    pub removed: Vec<SubscriptionInfo>,
+    /// Subscriptions left untouched, belonging to plants not listed or returned without a plant
+    pub ignored: Vec<SubscriptionInfo>,
+}
+
+impl WebhookReconciliation {
+    pub fn is_unchanged(&self) -> bool {
+        self.created.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl SmartherApi<Authorized> {
+    /// Makes each plant have exactly one subscription, notifying the given endpoint.
+    ///
+    /// Missing subscriptions are created before the stale ones are removed, so notifications keep flowing
+    /// while the endpoint changes. Subscriptions of plants not listed, or without a plant, are left untouched
+    /// and reported as ignored.
+    /// Calling it again with the same endpoints changes nothing.
+    pub async fn reconcile_webhooks<I, U>(&self, desired: I) -> Result<WebhookReconciliation, SmartherError>
+    where
+        I: IntoIterator<Item = (PlantId, U)>,
+        U: Into<String>,
+    {
+        let desired: HashMap<PlantId, String> = desired.into_iter()
+            .map(|(plant_id, endpoint_url)| (plant_id, endpoint_url.into()))
+            .collect();
+
+        let mut reconciliation = WebhookReconciliation::default();
+        let mut stale = Vec::new();
+        let mut subscribed = HashSet::new();
+        for subscription in self.get_webhooks().await? {
+            let Some((plant_id, endpoint_url)) = subscription.plant_id.as_ref()
+                .and_then(|plant_id| desired.get_key_value(plant_id)) else {
+                reconciliation.ignored.push(subscription);
+                continue;
+            };
+            if subscription.endpoint_url.as_ref() == Some(endpoint_url) && subscribed.insert(plant_id.clone()) {
+                reconciliation.kept.push(subscription);
+            } else {
+                stale.push((plant_id.clone(), subscription));
+            }
+        }
+
+        for (plant_id, endpoint_url) in desired {
+            if !subscribed.contains(&plant_id) {
+                let subscription = self.register_webhook(&plant_id, endpoint_url).await?;
+                reconciliation.created.push(subscription);
+            }
+        }
+
+        for (plant_id, subscription) in stale {
+            self.unregister_webhook(&plant_id, &subscription.subscription_id).await?;
+            reconciliation.removed.push(subscription);
+        }
+
+        Ok(reconciliation)
+    }
+}
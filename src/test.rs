@@ -1,6 +1,6 @@
 use crate::{
     model::*,
-    AuthorizationGrant, AuthorizationInfo, OAuthTokenRequest,
+    AuthorizationGrant, AuthorizationInfo, OAuthTokenRequest, SmartherApi, SmartherError,
 };
 
 #[test]
@@ -117,4 +117,479 @@ fn correctly_parse_c2c_events() {
     let events: C2CEvents = serde_json::from_str(&event_message_json).unwrap();
     assert!(events.len() == 1);
     assert!(events[0].data.chronothermostats.len() == 1);
-}
\ No newline at end of file
+    assert_eq!(events[0].data.chronothermostats[0].online, Some(false));
+}
+
+#[test]
+fn unknown_status_fields_are_preserved() {
+    let status_message_json = std::fs::read_to_string("validation/status_message.json").unwrap();
+    let status: ModuleStatus = serde_json::from_str(&status_message_json).unwrap();
+    assert_eq!(status.chronothermostats[0].online, None);
+    assert!(status.chronothermostats[0].extra.is_empty());
+
+    let mut raw_status: serde_json::Value = serde_json::from_str(&status_message_json).unwrap();
+    raw_status["chronothermostats"][0]["ecoMode"] = serde_json::json!({ "enabled": true });
+    let status: ModuleStatus = serde_json::from_value(raw_status).unwrap();
+    assert_eq!(status.chronothermostats[0].extra["ecoMode"], serde_json::json!({ "enabled": true }));
+
+    let serialized = serde_json::to_value(&status).unwrap();
+    assert_eq!(serialized["chronothermostats"][0]["ecoMode"]["enabled"], true);
+}
+
+#[test]
+fn unsupported_grant_is_rejected() {
+    let fake_info = &AuthorizationInfo {
+        grant: AuthorizationGrant::None,
+        client_id: "test".into(),
+        client_secret: "secret".into(),
+        subscription_key: "sub".into(),
+    };
+
+    let refresh_request: Result<OAuthTokenRequest, SmartherError> = fake_info.try_into();
+    assert!(matches!(refresh_request, Err(SmartherError::Authorization(_))));
+}
+
+#[test]
+fn expired_grant_has_no_request_token() {
+    let grant = AuthorizationGrant::OAuthToken {
+        access_token: "none".into(),
+        refresh_token: "refresh".into(),
+        expires_on: 0,
+    };
+
+    assert!(matches!(grant.request_token(), Err(SmartherError::AuthExpired)));
+}
+
+#[test]
+fn expired_authorization_is_accepted_for_refresh() {
+    let expired_info = AuthorizationInfo {
+        grant: AuthorizationGrant::OAuthToken {
+            access_token: "none".into(),
+            refresh_token: "refresh".into(),
+            expires_on: 0,
+        },
+        client_id: "test".into(),
+        client_secret: "secret".into(),
+        subscription_key: "sub".into(),
+    };
+
+    let client = SmartherApi::default().with_authorization(expired_info.clone()).unwrap();
+    assert_eq!(client.authorization_info(), expired_info);
+
+    let missing_info = AuthorizationInfo {
+        grant: AuthorizationGrant::None,
+        ..expired_info
+    };
+    assert!(SmartherApi::default().with_authorization(missing_info).is_err());
+}
+
+#[tokio::test]
+async fn token_stores_round_trip() {
+    use crate::store::{JsonFileTokenStore, MemoryTokenStore, TokenStore};
+
+    let auth_info = AuthorizationInfo {
+        grant: AuthorizationGrant::OAuthToken {
+            access_token: "access".into(),
+            refresh_token: "refresh".into(),
+            expires_on: 42,
+        },
+        client_id: "test".into(),
+        client_secret: "secret".into(),
+        subscription_key: "sub".into(),
+    };
+
+    let memory_store = MemoryTokenStore::default();
+    assert_eq!(memory_store.load().await.unwrap(), None);
+    memory_store.save(&auth_info).await.unwrap();
+    assert_eq!(memory_store.load().await.unwrap(), Some(auth_info.clone()));
+
+    let token_file = std::env::temp_dir().join(format!("smarther_tokens_{}.json", std::process::id()));
+    let file_store = JsonFileTokenStore::new(&token_file);
+    assert_eq!(file_store.load().await.unwrap(), None);
+    file_store.save(&auth_info).await.unwrap();
+    file_store.save(&auth_info).await.unwrap();
+    assert_eq!(file_store.load().await.unwrap(), Some(auth_info));
+    assert!(!token_file.with_extension("json.tmp").exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&token_file).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    std::fs::remove_file(token_file).unwrap();
+}
+
+#[test]
+fn correctly_parse_program_list() {
+    let program_list_json = std::fs::read_to_string("validation/program_list.json").unwrap();
+    let program_list: ProgramList = serde_json::from_str(&program_list_json).unwrap();
+    assert!(program_list.chronothermostats.len() == 1);
+    assert_eq!(
+        program_list.chronothermostats[0].programs,
+        vec![
+            Program { number: 1, name: "Winter".into() },
+            Program { number: 2, name: "Holidays".into() },
+        ]
+    );
+}
+
+#[test]
+fn status_requests_are_built_correctly() {
+    let manual = SetStatusRequest::manual(ThermostatFunction::Heating, Measurement::Celsius(21.5), None);
+    assert!(manual.validate().is_ok());
+    assert_eq!(serde_json::to_string(&manual).unwrap(), r#"{"function":"HEATING","mode":"MANUAL","setPoint":{"unit":"C","value":21.5}}"#);
+
+    let until = "2021-01-05T17:53:22.123Z".parse().unwrap();
+    let manual_until = SetStatusRequest::manual(ThermostatFunction::Cooling, Measurement::Celsius(24.0), Some(until));
+    assert_eq!(manual_until.activation_time(), Some(until));
+    assert!(manual_until.validate().is_err());
+    assert_eq!(serde_json::to_string(&manual_until).unwrap(), r#"{"function":"COOLING","mode":"MANUAL","setPoint":{"unit":"C","value":24.0},"activationTime":"2021-01-05T17:53:22Z"}"#);
+    let parsed: SetStatusRequest = serde_json::from_str(r#"{"function":"COOLING","mode":"MANUAL","setPoint":{"unit":"C","value":24.0},"activationTime":"2021-01-05T17:53:22Z"}"#).unwrap();
+    assert_eq!(parsed.activation_time(), Some("2021-01-05T17:53:22Z".parse().unwrap()));
+
+    let boost = SetStatusRequest::boost(ThermostatFunction::Heating, chrono::Duration::minutes(30));
+    assert!(boost.validate().is_ok());
+    assert!(SetStatusRequest::boost(ThermostatFunction::Heating, chrono::Duration::minutes(-30)).validate().is_err());
+    assert_eq!(boost.mode(), &ThermostatMode::Boost);
+    assert!(boost.activation_time().is_some());
+
+    let automatic = SetStatusRequest::automatic(ThermostatFunction::Heating, [1, 2]);
+    assert!(automatic.validate().is_ok());
+    assert_eq!(serde_json::to_string(&automatic).unwrap(), r#"{"function":"HEATING","mode":"AUTOMATIC","programs":[{"number":1},{"number":2}]}"#);
+
+    let off = SetStatusRequest::off(ThermostatFunction::Cooling);
+    assert_eq!(serde_json::to_string(&off).unwrap(), r#"{"function":"COOLING","mode":"OFF"}"#);
+    assert_eq!(SetStatusRequest::protection(ThermostatFunction::Heating).mode(), &ThermostatMode::Protection);
+}
+
+#[test]
+fn retry_backoff_is_exponential_and_honors_retry_after() {
+    use std::time::Duration;
+    use crate::retry::RetryPolicy;
+
+    let policy = RetryPolicy {
+        max_retries: 5,
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_secs(1),
+        ..Default::default()
+    };
+
+    assert_eq!(policy.backoff(0, None), Some(Duration::from_millis(100)));
+    assert_eq!(policy.backoff(2, None), Some(Duration::from_millis(400)));
+    assert_eq!(policy.backoff(10, None), Some(Duration::from_secs(1)));
+    assert_eq!(policy.backoff(0, Some(Duration::from_millis(700))), Some(Duration::from_millis(700)));
+    assert_eq!(policy.backoff(0, Some(Duration::from_secs(60))), None);
+    assert!(policy.allows(&reqwest::Method::GET));
+    assert!(!policy.allows(&reqwest::Method::POST));
+
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::RETRY_AFTER, "12".parse().unwrap());
+    assert_eq!(crate::error::retry_after(&headers), Some(Duration::from_secs(12)));
+    headers.insert(reqwest::header::RETRY_AFTER, "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap());
+    assert_eq!(crate::error::retry_after(&headers), Some(Duration::ZERO));
+}
+
+#[test]
+fn config_defaults_to_production_urls() {
+    use std::time::Duration;
+    use crate::{SmartherConfig, API_URL, AUTH_URL, TOKEN_URL};
+
+    let config = SmartherConfig::default();
+    assert_eq!(config.api_url, API_URL);
+    assert_eq!(config.auth_url, AUTH_URL);
+    assert_eq!(config.token_url, TOKEN_URL);
+
+    let sandbox = SmartherConfig::default()
+        .with_api_url("http://localhost:8080/smarther/v2.0")
+        .with_token_url("http://localhost:8080/token")
+        .with_timeout(Duration::from_secs(5));
+    assert_eq!(sandbox.auth_url, AUTH_URL);
+    assert_eq!(sandbox.timeout, Some(Duration::from_secs(5)));
+    assert!(SmartherApi::with_config(sandbox).is_ok());
+}
+
+#[test]
+fn unknown_enum_values_round_trip() {
+    let status_message_json = std::fs::read_to_string("validation/status_message.json").unwrap();
+    let mut raw_status: serde_json::Value = serde_json::from_str(&status_message_json).unwrap();
+    raw_status["chronothermostats"][0]["mode"] = "HOLIDAY".into();
+    raw_status["chronothermostats"][0]["function"] = "DRYING".into();
+    raw_status["chronothermostats"][0]["loadState"] = "STANDBY".into();
+
+    let status: ModuleStatus = serde_json::from_value(raw_status).unwrap();
+    let thermostat = &status.chronothermostats[0];
+    assert_eq!(thermostat.mode, ThermostatMode::Unknown("HOLIDAY".into()));
+    assert_eq!(thermostat.function, ThermostatFunction::Unknown("DRYING".into()));
+    assert_eq!(thermostat.load_state, Some(LoadState::Unknown("STANDBY".into())));
+
+    let serialized = serde_json::to_value(&status).unwrap();
+    assert_eq!(serialized["chronothermostats"][0]["mode"], "HOLIDAY");
+    assert_eq!(serde_json::to_string(&ThermostatMode::Boost).unwrap(), r#""BOOST""#);
+    assert_eq!(serde_json::from_str::<LoadState>(r#""ACTIVE""#).unwrap(), LoadState::Active);
+}
+
+#[test]
+fn measurements_convert_between_units() {
+    use std::cmp::Ordering;
+
+    assert_eq!(Measurement::Celsius(100.0).to_fahrenheit(), Some(Measurement::Fahrenheit(212.0)));
+    assert_eq!(Measurement::Fahrenheit(212.0).to_celsius(), Some(Measurement::Celsius(100.0)));
+    assert_eq!(Measurement::Celsius(20.0).in_unit(&MeasurementUnit::Celsius), Some(Measurement::Celsius(20.0)));
+    assert_eq!(Measurement::Percentage(50.0).in_unit(&MeasurementUnit::Percentage), Some(Measurement::Percentage(50.0)));
+    assert_eq!(Measurement::Percentage(50.0).to_celsius(), None);
+    assert_eq!(Measurement::Celsius(20.0).in_unit(&MeasurementUnit::Percentage), None);
+
+    assert_eq!(Measurement::Celsius(21.0).compare(&Measurement::Fahrenheit(69.8)), Some(Ordering::Equal));
+    assert_eq!(Measurement::Celsius(21.5).compare(&Measurement::Fahrenheit(69.8)), Some(Ordering::Greater));
+    assert_eq!(Measurement::Celsius(21.0).compare(&Measurement::Percentage(21.0)), None);
+}
+
+#[test]
+fn manual_request_uses_thermostat_unit() {
+    let status_message_json = std::fs::read_to_string("validation/status_message.json").unwrap();
+    let status: ModuleStatus = serde_json::from_str(&status_message_json).unwrap();
+    let mut thermostat = status.chronothermostats[0].clone();
+
+    let limits = SetPointLimits::default();
+
+    let request = thermostat.manual_request(Measurement::Fahrenheit(69.8), None, &limits).unwrap();
+    assert_eq!(request.set_point(), Some(&Measurement::Celsius(21.0)));
+    let request = thermostat.manual_request(Measurement::Fahrenheit(70.0), None, &limits).unwrap();
+    assert_eq!(request.set_point(), Some(&Measurement::Celsius(21.0)));
+    let fine_limits = SetPointLimits { step: 0.1, ..limits };
+    let request = thermostat.manual_request(Measurement::Fahrenheit(70.0), None, &fine_limits).unwrap();
+    assert_eq!(request.set_point(), Some(&Measurement::Celsius(21.1)));
+    assert!(request.validate_with_limits(&fine_limits).is_ok());
+
+    // Values already in the thermostat unit are not snapped, the step is left to the validation
+    let request = thermostat.manual_request(Measurement::Celsius(19.37), None, &limits).unwrap();
+    assert_eq!(request.set_point(), Some(&Measurement::Celsius(19.37)));
+    assert!(matches!(request.validate(), Err(SmartherError::Validation(_))));
+
+    thermostat.temperature_format = Some(MeasurementUnit::Fahrenheit);
+    let request = thermostat.manual_request(Measurement::Celsius(21.0), None, &limits).unwrap();
+    assert_eq!(request.set_point(), Some(&Measurement::Fahrenheit(70.0)));
+    assert_eq!(request.function(), &ThermostatFunction::Heating);
+    assert!(request.validate().is_ok());
+    for fahrenheit in [68.0, 69.0, 70.0, 71.0, 72.0] {
+        let request = thermostat.manual_request(Measurement::Fahrenheit(fahrenheit), None, &limits).unwrap();
+        assert_eq!(request.set_point(), Some(&Measurement::Fahrenheit(fahrenheit)));
+        assert!(request.validate().is_ok());
+    }
+
+    assert!(thermostat.manual_request(Measurement::Percentage(50.0), None, &limits).is_err());
+}
+
+#[test]
+fn set_points_are_validated_against_limits() {
+    let manual = |set_point| SetStatusRequest::manual(ThermostatFunction::Heating, set_point, None);
+
+    assert!(manual(Measurement::Celsius(21.5)).validate().is_ok());
+    for fahrenheit in 68..=72 {
+        assert!(manual(Measurement::Fahrenheit(fahrenheit as f32)).validate().is_ok());
+    }
+    assert!(matches!(manual(Measurement::Fahrenheit(69.8)).validate(), Err(SmartherError::Validation(_))));
+    assert!(matches!(manual(Measurement::Fahrenheit(105.0)).validate(), Err(SmartherError::Validation(_))));
+    assert!(matches!(manual(Measurement::Celsius(45.0)).validate(), Err(SmartherError::Validation(_))));
+    assert!(matches!(manual(Measurement::Celsius(19.37)).validate(), Err(SmartherError::Validation(_))));
+    assert!(matches!(manual(Measurement::Percentage(50.0)).validate(), Err(SmartherError::Validation(_))));
+    assert!(SetStatusRequest::automatic(ThermostatFunction::Heating, Vec::<u32>::new()).validate().is_err());
+
+    let module: Module = serde_json::from_value(serde_json::json!({
+        "device": "chronothermostat",
+        "name": "Living room",
+        "id": "1ee68d6f-46b7-8f11-e053-27182d0a846a",
+        "capabilities": [{ "minSetPoint": 5, "maxSetPoint": "30", "setPointStep": 1.0 }]
+    })).unwrap();
+    assert_eq!(module.set_point_limits(), SetPointLimits { min: 5.0, max: 30.0, step: 1.0, ..Default::default() });
+    assert!(manual(Measurement::Celsius(21.0)).validate_for(&module).is_ok());
+    assert!(manual(Measurement::Celsius(21.5)).validate_for(&module).is_err());
+    assert!(manual(Measurement::Celsius(35.0)).validate_for(&module).is_err());
+}
+
+#[test]
+fn module_capabilities_are_typed() {
+    let module: Module = serde_json::from_value(serde_json::json!({
+        "device": "chronothermostat",
+        "name": "Living room",
+        "id": "1ee68d6f-46b7-8f11-e053-27182d0a846a",
+        "capabilities": [
+            { "capability": "thermoregulation", "modes": ["AUTOMATIC", "MANUAL", "OFF"], "functions": ["HEATING"] },
+            { "capability": "features", "canDo": { "boost": true, "holiday": false }, "vendorFlag": 1 },
+            { "capability": "programs", "canDo": ["programList"] }
+        ]
+    })).unwrap();
+
+    assert!(module.supports(&ThermostatMode::Manual));
+    assert!(!module.supports(&ThermostatMode::Boost));
+    assert!(module.supports_function(&ThermostatFunction::Heating));
+    assert!(!module.supports_function(&ThermostatFunction::Cooling));
+    assert!(module.can_do("boost"));
+    assert!(module.can_do("programlist"));
+    assert!(!module.can_do("holiday"));
+
+    let capabilities = module.capabilities.as_ref().unwrap();
+    assert_eq!(capabilities[0].capability.as_deref(), Some("thermoregulation"));
+    assert_eq!(capabilities[1].extra["vendorFlag"], 1);
+
+    let undeclared = Module { capabilities: None, ..module };
+    assert!(undeclared.supports(&ThermostatMode::Boost));
+    assert!(undeclared.supports_function(&ThermostatFunction::Cooling));
+}
+
+#[test]
+fn identifiers_parse_cloud_prefix() {
+    let plant_id: PlantId = "bs_cloud_b5e48d6f-cbad-2711-e053-27182d0ad74c".parse().unwrap();
+    assert_eq!(plant_id, PlantId::new("b5e48d6f-cbad-2711-e053-27182d0ad74c"));
+    assert_eq!(plant_id.to_string(), "b5e48d6f-cbad-2711-e053-27182d0ad74c");
+    assert_eq!(PlantId::from_subject("///bs_cloud_b5e48d6f-cbad-2711-e053-27182d0ad74c//"), Some(plant_id.clone()));
+    assert_eq!(PlantId::from_subject("///"), None);
+    assert_eq!(serde_json::to_string(&ModuleId::new("module")).unwrap(), r#""module""#);
+    assert_eq!(serde_json::from_str::<PlantId>(r#""bs_cloud_b5e48d6f-cbad-2711-e053-27182d0ad74c""#).unwrap(), plant_id);
+    assert_eq!(ModuleId::new("bs_cloud_module").as_str(), "bs_cloud_module");
+
+    let event_message_json = std::fs::read_to_string("validation/c2c_event.json").unwrap();
+    let events: C2CEvents = serde_json::from_str(&event_message_json).unwrap();
+    assert_eq!(events[0].plant_id(), Some(plant_id.clone()));
+
+    let sender = events[0].data.chronothermostats[0].sender.as_ref().unwrap();
+    let sender_plant = sender.plant.as_ref().unwrap();
+    assert_eq!(sender_plant.id, plant_id);
+    assert_eq!(sender_plant.module.id, ModuleId::new("1ee68d6f-46b7-8f11-e053-27182d0a846a"));
+}
+
+#[test]
+fn status_changes_are_detected() {
+    use crate::events::{diff_status, StatusTracker, ThermostatEvent};
+
+    let status_message_json = std::fs::read_to_string("validation/status_message.json").unwrap();
+    let status: ModuleStatus = serde_json::from_str(&status_message_json).unwrap();
+    let previous = status.chronothermostats[0].clone();
+
+    let initial = diff_status(None, &previous);
+    assert_eq!(initial.len(), 5);
+    assert!(!initial.contains(&ThermostatEvent::WentOffline));
+    assert!(diff_status(Some(&previous), &previous).is_empty());
+
+    let mut off = previous.clone();
+    off.mode = ThermostatMode::Off;
+    off.set_point = None;
+    off.load_state = None;
+    let initial_off = diff_status(None, &off);
+    assert_eq!(initial_off.len(), 3);
+    assert!(!initial_off.iter().any(|event| matches!(
+        event,
+        ThermostatEvent::SetPointChanged { .. } | ThermostatEvent::LoadStateChanged { .. }
+    )));
+
+    let mut current = previous.clone();
+    current.mode = ThermostatMode::Manual;
+    current.set_point = Some(Measurement::Celsius(21.0));
+    current.load_state = Some(LoadState::Active);
+    assert_eq!(diff_status(Some(&previous), &current), vec![
+        ThermostatEvent::ModeChanged { previous: Some(ThermostatMode::Automatic), current: ThermostatMode::Manual },
+        ThermostatEvent::SetPointChanged { previous: Some(Measurement::Celsius(18.0)), current: Some(Measurement::Celsius(21.0)) },
+        ThermostatEvent::LoadStateChanged { previous: Some(LoadState::Inactive), current: Some(LoadState::Active) },
+    ]);
+
+    let measured = TimedMeasurement {
+        time_stamp: "2021-02-27T21:00:00Z".parse().unwrap(),
+        value: Measurement::Celsius(20.0),
+    };
+    let mut current = previous.clone();
+    current.thermometer = Some(Instrument { measures: Some(vec![measured.clone()]) });
+    assert_eq!(diff_status(Some(&previous), &current), vec![ThermostatEvent::TemperatureMeasured(measured)]);
+
+    let mut tracker = StatusTracker::default();
+    let plant_id = PlantId::new("plant");
+    let module_id = ModuleId::new("module");
+    assert_eq!(tracker.update(&plant_id, &module_id, previous.clone()).len(), 5);
+    assert!(tracker.update(&plant_id, &module_id, previous.clone()).is_empty());
+    assert_eq!(tracker.last_status(&plant_id, &module_id), Some(&previous));
+}
+
+#[tokio::test]
+async fn c2c_events_share_the_polling_event_model() {
+    use futures::StreamExt;
+    use crate::events::{module_events, StatusTracker, ThermostatEvent};
+
+    let event_message_json = std::fs::read_to_string("validation/c2c_event.json").unwrap();
+    let events: C2CEvents = serde_json::from_str(&event_message_json).unwrap();
+    let plant_id = PlantId::new("b5e48d6f-cbad-2711-e053-27182d0ad74c");
+    let module_id = ModuleId::new("1ee68d6f-46b7-8f11-e053-27182d0a846a");
+
+    let mut without_subject = events[0].clone();
+    without_subject.subject = "///".into();
+    assert_eq!(without_subject.plant_id(), Some(plant_id.clone()));
+    let statuses: Vec<_> = events[0].module_statuses().collect();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!((statuses[0].0, statuses[0].1), (&plant_id, &module_id));
+
+    let mut tracker = StatusTracker::default();
+    let initial = tracker.apply_c2c_event(&events[0]);
+    assert_eq!(initial[0].event, ThermostatEvent::WentOffline);
+    assert!(initial.iter().all(|event| event.plant_id == plant_id && event.module_id == module_id));
+    assert!(tracker.apply_c2c_event(&events[0]).is_empty());
+
+    let mut back_online = events[0].clone();
+    back_online.id = "back-online".into();
+    back_online.data.chronothermostats[0].online = Some(true);
+    back_online.data.chronothermostats[0].mode = ThermostatMode::Off;
+    let notifications = futures::stream::iter(vec![events.clone(), vec![back_online]]);
+    let received: Vec<_> = module_events(notifications).map(|event| event.event).collect().await;
+    assert_eq!(received.len(), initial.len() + 1);
+    assert_eq!(received.last(), Some(&ThermostatEvent::ModeChanged {
+        previous: Some(ThermostatMode::Automatic),
+        current: ThermostatMode::Off,
+    }));
+}
+
+#[test]
+fn event_id_cache_forgets_oldest_ids() {
+    use crate::events::EventIdCache;
+
+    let mut cache = EventIdCache::new(2);
+    assert!(cache.insert("a"));
+    assert!(!cache.insert("a"));
+    assert!(cache.insert("b"));
+    assert!(cache.insert("c"));
+    assert!(!cache.contains("a"));
+    assert!(cache.contains("b") && cache.contains("c"));
+    cache.remove("b");
+    assert!(cache.insert("b"));
+}
+
+#[test]
+fn redelivered_and_late_c2c_events_are_dropped() {
+    use crate::events::{StatusTracker, ThermostatEvent};
+
+    let event_message_json = std::fs::read_to_string("validation/c2c_event.json").unwrap();
+    let events: C2CEvents = serde_json::from_str(&event_message_json).unwrap();
+    let mut tracker = StatusTracker::default().with_event_dedup(16);
+    let first = events[0].clone();
+    assert!(!tracker.apply_c2c_event(&first).is_empty());
+    assert!(tracker.apply_c2c_event(&first).is_empty());
+
+    let mut newer = first.clone();
+    newer.id = "newer".into();
+    newer.event_time += chrono::Duration::minutes(2);
+    newer.data.chronothermostats[0].time += chrono::Duration::minutes(2);
+    newer.data.chronothermostats[0].mode = ThermostatMode::Off;
+    let mut late = first.clone();
+    late.id = "late".into();
+    late.event_time += chrono::Duration::minutes(1);
+    late.data.chronothermostats[0].time += chrono::Duration::minutes(1);
+    late.data.chronothermostats[0].mode = ThermostatMode::Manual;
+
+    let changes: Vec<_> = tracker.apply_c2c_event(&newer).into_iter().map(|event| event.event).collect();
+    assert_eq!(changes, vec![ThermostatEvent::ModeChanged {
+        previous: Some(ThermostatMode::Automatic),
+        current: ThermostatMode::Off,
+    }]);
+    assert!(tracker.apply_c2c_event(&late).is_empty());
+
+    let plant_id = first.plant_id().unwrap();
+    let module_id = ModuleId::new("1ee68d6f-46b7-8f11-e053-27182d0a846a");
+    let stale_poll = first.data.chronothermostats[0].clone();
+    assert!(tracker.update(&plant_id, &module_id, stale_poll).is_empty());
+    assert_eq!(tracker.last_status(&plant_id, &module_id).unwrap().mode, ThermostatMode::Off);
+}
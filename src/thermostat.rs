@@ -0,0 +1,103 @@
+use std::sync::RwLock;
+
+use crate::{
+    model::{Measurement, ModuleId, PlantId, SetPointLimits, SetStatusRequest, ThermostatFunction, ThermostatStatus},
+    states::Authorized,
+    SmartherApi, SmartherError,
+};
+
+/// Handle to a single chronothermostat, caching the last status read from the API.
+pub struct Thermostat<'a> {
+    api: &'a SmartherApi<Authorized>,
+    plant_id: PlantId,
+    module_id: ModuleId,
+    limits: SetPointLimits,
+    last_status: RwLock<Option<ThermostatStatus>>,
+}
+
+impl<'a> Thermostat<'a> {
+    pub(crate) fn new(api: &'a SmartherApi<Authorized>, plant_id: PlantId, module_id: ModuleId) -> Self {
+        Self {
+            api,
+            plant_id,
+            module_id,
+            limits: SetPointLimits::default(),
+            last_status: RwLock::new(None),
+        }
+    }
+
+    /// Set point limits used by `set_manual`, usually the ones advertised by the module through `Module::set_point_limits`.
+    pub fn with_limits(mut self, limits: SetPointLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn plant_id(&self) -> &PlantId {
+        &self.plant_id
+    }
+
+    pub fn module_id(&self) -> &ModuleId {
+        &self.module_id
+    }
+
+    /// Last status read, without contacting the API.
+    pub fn cached_status(&self) -> Option<ThermostatStatus> {
+        self.last_status.read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Returns the cached status, fetching it only if nothing was read yet.
+    pub async fn status(&self) -> Result<ThermostatStatus, SmartherError> {
+        match self.cached_status() {
+            Some(status) => Ok(status),
+            None => self.refresh().await,
+        }
+    }
+
+    /// Fetches the current status, updating the cache.
+    pub async fn refresh(&self) -> Result<ThermostatStatus, SmartherError> {
+        let module_status = self.api.get_device_status(&self.plant_id, &self.module_id).await?;
+        let status = module_status.chronothermostats.into_iter().next()
+            .ok_or_else(|| SmartherError::MissingData(format!("No status returned for module {}", self.module_id)))?;
+        self.set_cached_status(Some(status.clone()));
+        Ok(status)
+    }
+
+    /// Holds the given temperature, converted to the unit configured on the thermostat and checked against its limits.
+    pub async fn set_manual(&self, temperature: Measurement) -> Result<(), SmartherError> {
+        let request = self.status().await?.manual_request(temperature, None, &self.limits)?;
+        request.validate_with_limits(&self.limits)?;
+        self.apply(request).await
+    }
+
+    pub async fn boost(&self, duration: chrono::Duration) -> Result<(), SmartherError> {
+        let request = SetStatusRequest::boost(self.function().await?, duration);
+        self.apply(request).await
+    }
+
+    pub async fn off(&self) -> Result<(), SmartherError> {
+        let request = SetStatusRequest::off(self.function().await?);
+        self.apply(request).await
+    }
+
+    pub async fn auto(&self, program: u32) -> Result<(), SmartherError> {
+        let request = SetStatusRequest::automatic(self.function().await?, [program]);
+        self.apply(request).await
+    }
+
+    async fn function(&self) -> Result<ThermostatFunction, SmartherError> {
+        Ok(self.status().await?.function)
+    }
+
+    /// Sends the request, invalidating the cache since the status changed.
+    pub async fn apply(&self, request: SetStatusRequest) -> Result<(), SmartherError> {
+        self.api.set_device_status(&self.plant_id, &self.module_id, request).await?;
+        self.set_cached_status(None);
+        Ok(())
+    }
+
+    fn set_cached_status(&self, status: Option<ThermostatStatus>) {
+        *self.last_status.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = status;
+    }
+}
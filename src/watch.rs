@@ -0,0 +1,53 @@
+use std::{collections::VecDeque, time::Duration};
+
+use futures::Stream;
+
+use crate::{
+    events::{ModuleEvent, StatusTracker},
+    model::{ModuleId, PlantId},
+    states::Authorized,
+    SmartherApi, SmartherError,
+};
+
+struct WatchState {
+    tracker: StatusTracker,
+    pending: VecDeque<Result<ModuleEvent, SmartherError>>,
+    first_poll: bool,
+}
+
+impl SmartherApi<Authorized> {
+    /// Polls the module status every `interval`, yielding the changes found between successive polls.
+    ///
+    /// The first poll reports the current state. Failed polls are yielded as errors and polling goes on.
+    pub fn watch(&self, plant_id: PlantId, module_id: ModuleId, interval: Duration) -> impl Stream<Item = Result<ModuleEvent, SmartherError>> + '_ {
+        let state = WatchState {
+            tracker: StatusTracker::default(),
+            pending: VecDeque::new(),
+            first_poll: true,
+        };
+
+        futures::stream::unfold(state, move |mut state| {
+            let plant_id = plant_id.clone();
+            let module_id = module_id.clone();
+            async move {
+                while state.pending.is_empty() {
+                    if !state.first_poll {
+                        tokio::time::sleep(interval).await;
+                    }
+                    state.first_poll = false;
+
+                    match self.get_device_status(&plant_id, &module_id).await {
+                        Ok(module_status) => {
+                            for status in module_status.chronothermostats {
+                                let events = state.tracker.update(&plant_id, &module_id, status);
+                                state.pending.extend(events.into_iter().map(Ok));
+                            }
+                        },
+                        Err(err) => state.pending.push_back(Err(err)),
+                    }
+                }
+                state.pending.pop_front().map(|event| (event, state))
+            }
+        })
+    }
+}
@@ -19,8 +19,20 @@ async fn tokens(query: Query<AuthenticationResponse>, data: Data<AuthState>) ->
     extract_tokens(&query, &data.csrf_token, &data.auth_channel).await
 }
 
-async fn extract_tokens(auth_info: &AuthenticationResponse, csrf_token: &String, auth_channel: &Sender<anyhow::Result<String>>) -> &'static str {
-    let result = match &auth_info {
+async fn extract_tokens(auth_info: &AuthenticationResponse, csrf_token: &str, auth_channel: &Sender<anyhow::Result<String>>) -> &'static str {
+    let result = check_authorization(auth_info, csrf_token);
+    
+    if result.is_ok() {
+        auth_channel.send(result).await.unwrap();
+        "Authorized! You can close this window now."
+    } else {
+        auth_channel.send(Err(anyhow::anyhow!("Error during authorization"))).await.unwrap();
+        "Error during authorization"
+    }
+}
+
+fn check_authorization(auth_info: &AuthenticationResponse, csrf_token: &str) -> anyhow::Result<String> {
+    match &auth_info {
         AuthenticationResponse {
             code: Some(code), 
             state: Some(state) 
@@ -32,14 +44,21 @@ async fn extract_tokens(auth_info: &AuthenticationResponse, csrf_token: &String,
             } 
         },
         _ => Err(anyhow::anyhow!("Error during authorization"))
-    };
-    
-    if result.is_ok() {
-        auth_channel.send(result).await.unwrap();
-        "Authorized! You can close this window now."
-    } else {
-        auth_channel.send(Err(anyhow::anyhow!("Error during authorization"))).await.unwrap();
-        "Error during authorization"
+    }
+}
+
+/// Extracts the code from what the user pasted in the headless flow: either the URL they were redirected to,
+/// whose state is checked as done by the `/tokens` route, or the bare code.
+pub(crate) fn parse_pasted_response(input: &str, csrf_token: &str) -> anyhow::Result<String> {
+    let input = input.trim();
+    match reqwest::Url::parse(input) {
+        Ok(redirect_url) => {
+            let query = Query::<AuthenticationResponse>::from_query(redirect_url.query().unwrap_or_default())
+                .map_err(|_| anyhow::anyhow!("Error during authorization"))?;
+            check_authorization(&query, csrf_token)
+        },
+        Err(_) if !input.is_empty() && !input.contains(char::is_whitespace) => Ok(input.to_string()),
+        Err(_) => Err(anyhow::anyhow!("Expected the redirect URL or the authorization code")),
     }
 }
 
@@ -69,4 +88,31 @@ mod test {
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn pasted_responses_are_checked() {
+        let csrf_token = "48da44ff-98fe-40c8-9a55-ac186decbf6f";
+        let redirect_url = format!("http://localhost:8989/tokens?code=test_token&state={csrf_token}");
+
+        assert_eq!(super::parse_pasted_response(&redirect_url, csrf_token).unwrap(), "test_token");
+        assert_eq!(super::parse_pasted_response(" test_token\n", csrf_token).unwrap(), "test_token");
+        assert!(super::parse_pasted_response("http://localhost:8989/tokens?code=test_token&state=forged", csrf_token).is_err());
+        assert!(super::parse_pasted_response("http://localhost:8989/tokens?error=access_denied", csrf_token).is_err());
+        assert!(super::parse_pasted_response("", csrf_token).is_err());
+    }
+
+    #[tokio::test]
+    async fn headless_flow_checks_the_state() {
+        let client = crate::SmartherApi::default();
+        let auth_info = client.get_oauth_access_code_with("id", "secret", "http://localhost/tokens", "sub", |oauth_link| async move {
+            let state = oauth_link.split("state=").nth(1).unwrap().split('&').next().unwrap().to_string();
+            Ok(format!("http://localhost/tokens?code=pasted_code&state={state}"))
+        }).await.unwrap();
+        assert_eq!(auth_info.grant, crate::AuthorizationGrant::AccessCode { access_code: "pasted_code".into() });
+
+        let forged = client.get_oauth_access_code_with("id", "secret", "http://localhost/tokens", "sub", |_| async {
+            Ok("http://localhost/tokens?code=pasted_code&state=forged".to_string())
+        }).await;
+        assert!(matches!(forged, Err(crate::SmartherError::Authorization(_))));
+    }
+}
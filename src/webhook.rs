@@ -0,0 +1,337 @@
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex}, time::Duration};
+
+use actix_web::{web::{self, Bytes, Data, Query, ServiceConfig}, App, HttpRequest, HttpResponse, HttpServer};
+use async_channel::{Receiver, Sender};
+use chrono::Utc;
+use log::warn;
+use reqwest::Url;
+
+use crate::{
+    events::EventIdCache,
+    model::{C2CEvent, C2CEvents, PlantId},
+    SmartherError,
+};
+
+/// Query parameter carrying the shared secret in the registered endpoint URL.
+pub const TOKEN_PARAMETER: &str = "token";
+
+/// Checks applied to the incoming notifications, nothing is checked by default.
+#[derive(Debug, Clone, Default)]
+pub struct WebhookVerification {
+    token: Option<String>,
+    known_plants: Option<HashSet<PlantId>>,
+    seen_events: Option<Arc<Mutex<EventIdCache>>>,
+    max_event_age: Option<Duration>,
+    max_clock_skew: Option<Duration>,
+}
+
+impl WebhookVerification {
+    /// Requires the secret as `token` query parameter, register the URL built by [`Self::endpoint_url`] for it to be sent back.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Rejects the events sent by modules of any other plant.
+    pub fn with_known_plants(mut self, plants: impl IntoIterator<Item = PlantId>) -> Self {
+        self.known_plants = Some(plants.into_iter().collect());
+        self
+    }
+
+    /// Drops the events whose id was already received, remembering the last `capacity` ids.
+    pub fn with_replay_protection(mut self, capacity: usize) -> Self {
+        self.seen_events = Some(Arc::new(Mutex::new(EventIdCache::new(capacity))));
+        self
+    }
+
+    /// Rejects the events older than `max_age`, or more than `max_clock_skew` in the future.
+    pub fn with_max_event_age(mut self, max_age: Duration, max_clock_skew: Duration) -> Self {
+        self.max_event_age = Some(max_age);
+        self.max_clock_skew = Some(max_clock_skew);
+        self
+    }
+
+    /// Adds the shared secret to the endpoint URL to register through `register_webhook`.
+    pub fn endpoint_url(&self, base_url: &str) -> Result<String, SmartherError> {
+        let mut url = Url::parse(base_url)
+            .map_err(|err| SmartherError::Validation(format!("Invalid endpoint URL {base_url}: {err}")))?;
+        if let Some(token) = &self.token {
+            url.query_pairs_mut().append_pair(TOKEN_PARAMETER, token);
+        }
+        Ok(url.into())
+    }
+
+    fn check_token(&self, request: &HttpRequest) -> bool {
+        let Some(token) = &self.token else {
+            return true;
+        };
+        Query::<HashMap<String, String>>::from_query(request.query_string())
+            .ok()
+            .and_then(|query| query.get(TOKEN_PARAMETER).map(|received| constant_time_eq(received.as_bytes(), token.as_bytes())))
+            .unwrap_or(false)
+    }
+
+    fn check_event(&self, event: &C2CEvent) -> Result<(), String> {
+        if let Some(known_plants) = &self.known_plants {
+            let senders_known = event.data.chronothermostats.iter().all(|status| {
+                status.sender.as_ref()
+                    .and_then(|sender| sender.plant.as_ref())
+                    .is_some_and(|plant| known_plants.contains(&plant.id))
+            });
+            if !senders_known || event.plant_id().is_some_and(|plant_id| !known_plants.contains(&plant_id)) {
+                return Err(format!("C2C event {} comes from an unknown plant", event.id));
+            }
+        }
+
+        let age = Utc::now() - event.event_time;
+        let too_old = self.max_event_age.is_some_and(|max_age| age.to_std().is_ok_and(|age| age > max_age));
+        let too_new = self.max_clock_skew.is_some_and(|max_skew| (-age).to_std().is_ok_and(|ahead| ahead > max_skew));
+        if too_old || too_new {
+            return Err(format!("C2C event {} has an implausible time {}", event.id, event.event_time));
+        }
+        Ok(())
+    }
+
+    /// Keeps only the events not seen yet, recording their ids.
+    fn drop_replayed(&self, events: &mut C2CEvents) {
+        let Some(seen_events) = &self.seen_events else {
+            return;
+        };
+        let mut seen_events = seen_events.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        events.retain(|event| {
+            let first_seen = seen_events.insert(&event.id);
+            if !first_seen {
+                warn!("Dropped replayed C2C event {}", event.id);
+            }
+            first_seen
+        });
+    }
+
+    /// Forgets the ids of events that could not be delivered, so that their redelivery is accepted.
+    fn forget(&self, events: &C2CEvents) {
+        let Some(seen_events) = &self.seen_events else {
+            return;
+        };
+        let mut seen_events = seen_events.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        for event in events {
+            seen_events.remove(&event.id);
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Receives the C2C notifications posted by Legrand to the endpoints registered through `register_webhook`.
+///
+/// Every accepted request is delivered as a batch on the returned [`Receiver`], which can also be consumed as a `Stream`.
+#[derive(Debug, Clone)]
+pub struct WebhookReceiver {
+    path: String,
+    events: Sender<C2CEvents>,
+    verification: WebhookVerification,
+}
+
+impl WebhookReceiver {
+    pub fn new(path: impl Into<String>, capacity: usize) -> (Self, Receiver<C2CEvents>) {
+        let (tx, rx) = async_channel::bounded(capacity);
+        (Self { path: path.into(), events: tx, verification: WebhookVerification::default() }, rx)
+    }
+
+    pub fn with_verification(mut self, verification: WebhookVerification) -> Self {
+        self.verification = verification;
+        self
+    }
+
+    /// Registers the receiver route, to be used with `App::configure` when embedding it in an existing server.
+    pub fn configure(&self, config: &mut ServiceConfig) {
+        config
+            .app_data(Data::new(self.clone()))
+            .route(&self.path, web::post().to(receive_events));
+    }
+
+    /// Runs a standalone server hosting only the receiver route.
+    pub async fn run(self, listen_config: (&str, u16)) -> std::io::Result<()> {
+        HttpServer::new(move || {
+            let receiver = self.clone();
+            App::new().configure(move |config| receiver.configure(config))
+        })
+        .bind(listen_config)?
+        .run()
+        .await
+    }
+}
+
+async fn receive_events(request: HttpRequest, body: Bytes, receiver: Data<WebhookReceiver>) -> HttpResponse {
+    let verification = &receiver.verification;
+    if !verification.check_token(&request) {
+        warn!("Rejected C2C notification without a valid token");
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let mut events: C2CEvents = match serde_json::from_slice(&body) {
+        Ok(events) => events,
+        Err(err) => {
+            warn!("Rejected malformed C2C notification: {}", err);
+            return HttpResponse::BadRequest().body("Malformed C2C events");
+        }
+    };
+
+    if let Some(invalid_event) = events.iter().find(|event| !is_valid_event(event)) {
+        warn!("Rejected invalid C2C event {}", invalid_event.id);
+        return HttpResponse::BadRequest().body("Invalid C2C event");
+    }
+
+    if let Err(reason) = events.iter().try_for_each(|event| verification.check_event(event)) {
+        warn!("Rejected C2C notification: {}", reason);
+        return HttpResponse::Forbidden().finish();
+    }
+
+    // Replays are acknowledged so that redeliveries of already processed events are not retried
+    verification.drop_replayed(&mut events);
+    if events.is_empty() {
+        return HttpResponse::Ok().finish();
+    }
+
+    match receiver.events.send(events).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(async_channel::SendError(events)) => {
+            verification.forget(&events);
+            HttpResponse::ServiceUnavailable().finish()
+        },
+    }
+}
+
+fn is_valid_event(event: &C2CEvent) -> bool {
+    !event.id.is_empty() && !event.data.chronothermostats.is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use actix_web::{test, App, http::StatusCode};
+
+    use super::{WebhookReceiver, WebhookVerification};
+    use crate::model::{C2CEvents, PlantId};
+
+    fn fresh_events() -> C2CEvents {
+        let payload = std::fs::read_to_string("validation/c2c_event.json").unwrap();
+        let mut events: C2CEvents = serde_json::from_str(&payload).unwrap();
+        events[0].event_time = chrono::Utc::now();
+        events
+    }
+
+    #[actix_web::test]
+    async fn events_are_delivered() {
+        let (receiver, events) = WebhookReceiver::new("/notify", 1);
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+
+        let payload = std::fs::read_to_string("validation/c2c_event.json").unwrap();
+        let request = test::TestRequest::post()
+            .uri("/notify")
+            .set_payload(payload)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let received = events.recv().await.unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].id, "495def51-34ad-aa94-9fff-24562f3eb5cc");
+    }
+
+    #[actix_web::test]
+    async fn malformed_events_are_rejected() {
+        let (receiver, events) = WebhookReceiver::new("/notify", 1);
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+
+        let request = test::TestRequest::post()
+            .uri("/notify")
+            .set_payload(r#"[{"id": "missing fields"}]"#)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(events.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn token_is_required() {
+        let verification = WebhookVerification::default().with_token("s3cret");
+        assert_eq!(
+            verification.endpoint_url("https://example.com/notify").unwrap(),
+            "https://example.com/notify?token=s3cret"
+        );
+        let (receiver, events) = WebhookReceiver::new("/notify", 1);
+        let receiver = receiver.with_verification(verification);
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+
+        for uri in ["/notify", "/notify?token=guess"] {
+            let request = test::TestRequest::post().uri(uri).set_json(fresh_events()).to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+        }
+        assert!(events.is_empty());
+
+        let request = test::TestRequest::post().uri("/notify?token=s3cret").set_json(fresh_events()).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+        assert_eq!(events.recv().await.unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn implausible_events_are_rejected() {
+        let verification = WebhookVerification::default()
+            .with_known_plants([PlantId::new("b5e48d6f-cbad-2711-e053-27182d0ad74c")])
+            .with_max_event_age(Duration::from_secs(300), Duration::from_secs(30));
+        let (receiver, events) = WebhookReceiver::new("/notify", 1);
+        let receiver = receiver.with_verification(verification);
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+
+        let mut unknown_sender = fresh_events();
+        unknown_sender[0].data.chronothermostats[0].sender.as_mut().unwrap().plant.as_mut().unwrap().id = "intruder".into();
+        let mut stale = fresh_events();
+        stale[0].event_time -= chrono::Duration::hours(1);
+        let mut future = fresh_events();
+        future[0].event_time += chrono::Duration::minutes(5);
+
+        for payload in [unknown_sender, stale, future] {
+            let request = test::TestRequest::post().uri("/notify").set_json(payload).to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(events.is_empty());
+
+        let request = test::TestRequest::post().uri("/notify").set_json(fresh_events()).to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn replayed_events_are_dropped() {
+        let (receiver, events) = WebhookReceiver::new("/notify", 2);
+        let receiver = receiver.with_verification(WebhookVerification::default().with_replay_protection(16));
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+
+        let payload = fresh_events();
+        for _ in 0..2 {
+            let request = test::TestRequest::post().uri("/notify").set_json(&payload).to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+        }
+
+        assert_eq!(events.recv().await.unwrap(), payload);
+        assert!(events.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn undelivered_events_are_not_recorded() {
+        let (receiver, events) = WebhookReceiver::new("/notify", 1);
+        let receiver = receiver.with_verification(WebhookVerification::default().with_replay_protection(16));
+        let app = test::init_service(App::new().configure(|config| receiver.configure(config))).await;
+        events.close();
+
+        // The redelivery must fail the same way instead of being acknowledged as a replay
+        let payload = fresh_events();
+        for _ in 0..2 {
+            let request = test::TestRequest::post().uri("/notify").set_json(&payload).to_request();
+            assert_eq!(test::call_service(&app, request).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+}